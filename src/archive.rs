@@ -0,0 +1,184 @@
+use std::borrow::Cow;
+
+use chrono::Utc;
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    http::AttachmentType,
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use crate::db::{ArchiveFormat, DatabaseServer};
+use crate::i18n::{self, tr};
+use crate::party_groups::Group;
+
+// Past this, older messages are left out of the transcript.
+const MAX_TRANSCRIPT_MESSAGES: usize = 1000;
+
+// Posts a transcript of the party's text channel and a summary of the party to the guild's archive
+// channel, if it has one. Has to run before the party's channels get deleted.
+pub(crate) async fn archive_party(ctx: &Context, guild: GuildId, group: &Group) -> CommandResult {
+    let settings = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?.settings;
+    let archive_channel = match settings.archive_channel {
+        Some(channel) => ChannelId(channel as u64),
+        None => return Ok(())
+    };
+
+    let language = i18n::guild_language(&settings);
+    let messages = fetch_messages(ctx, ChannelId(group.text_id as u64)).await?;
+    let (transcript, filename) = match settings.archive_format {
+        ArchiveFormat::Text => (
+            transcript_text(language, group, &messages),
+            format!("{}.txt", group.role_id)
+        ),
+        ArchiveFormat::Html => (transcript_html(group, &messages), format!("{}.html", group.role_id))
+    };
+
+    let files = vec![AttachmentType::Bytes {
+        data: Cow::from(transcript.into_bytes()),
+        filename
+    }];
+
+    archive_channel.send_files(&ctx.http, files, |cm| {
+        cm.embed(|ce| {
+            ce.title(tr(language, "archive.ended", &[("title", &group.title())]));
+            ce.field(tr(language, "archive.game", &[]), group.game(), true);
+            ce.field(tr(language, "archive.owner", &[]), UserId(group.owner as u64).mention(), true);
+            ce.field(
+                tr(language, "archive.members", &[]),
+                group.players().unwrap_or_else(|| tr(language, "party.nobody", &[])),
+                false
+            );
+            if group.created_at > 0 {
+                ce.field(
+                    tr(language, "archive.duration", &[]),
+                    format_duration(Utc::now().timestamp() - group.created_at),
+                    true
+                );
+            }
+            ce.field(tr(language, "archive.messages", &[]), messages.len(), true);
+            ce.colour(Colour::DARK_GREY);
+            ce.timestamp(&Utc::now());
+            ce
+        });
+        cm
+    }).await?;
+
+    Ok(())
+}
+
+// Oldest message first.
+async fn fetch_messages(ctx: &Context, channel: ChannelId) -> serenity::Result<Vec<Message>> {
+    let mut messages: Vec<Message> = Vec::new();
+    let mut before: Option<MessageId> = None;
+
+    while messages.len() < MAX_TRANSCRIPT_MESSAGES {
+        let batch = channel.messages(&ctx.http, |retriever| {
+            match before {
+                Some(before) => retriever.before(before).limit(100),
+                None => retriever.limit(100)
+            }
+        }).await?;
+
+        let done = batch.len() < 100;
+        before = batch.last().map(|message| message.id);
+        messages.extend(batch);
+
+        if done {
+            break
+        }
+    }
+
+    messages.reverse();
+    Ok(messages)
+}
+
+fn transcript_text(language: &str, group: &Group, messages: &[Message]) -> String {
+    let header = tr(language, "archive.transcript", &[("title", &group.title()), ("game", &group.game())]);
+    let mut transcript = format!("{}\n\n", header);
+
+    for message in messages {
+        transcript.push_str(&format!(
+            "[{}] {}: {}\n",
+            message.timestamp.format("%Y-%m-%d %H:%M"),
+            message.author.name,
+            message.content
+        ));
+
+        for attachment in message.attachments.iter() {
+            transcript.push_str(&format!("    {}\n", attachment.url));
+        }
+    }
+
+    transcript
+}
+
+fn transcript_html(group: &Group, messages: &[Message]) -> String {
+    let mut transcript = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n\
+        <body>\n<h1>{0}</h1>\n<h2>{1}</h2>\n",
+        escape_html(group.title()),
+        escape_html(group.game())
+    );
+
+    for message in messages {
+        transcript.push_str(&format!(
+            "<p><small>{}</small> <b>{}</b>: {}",
+            message.timestamp.format("%Y-%m-%d %H:%M"),
+            escape_html(&message.author.name),
+            escape_html(&message.content)
+        ));
+
+        for attachment in message.attachments.iter() {
+            let url = escape_html(&attachment.url);
+            transcript.push_str(&format!("<br><a href=\"{0}\">{0}</a>", url));
+        }
+
+        transcript.push_str("</p>\n");
+    }
+
+    transcript.push_str("</body>\n</html>\n");
+    transcript
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn format_duration(secs: i64) -> String {
+    let mins = secs.max(0) / 60;
+    format!("{}h {}m", mins / 60, mins % 60)
+}
+
+#[command]
+#[description = "Posts a transcript of every party that ends in a channel, or turns it off."]
+#[usage = "<#channel> [text|html] | off"]
+#[example = "#party-archive html"]
+async fn archive(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let language = i18n::language_for(ctx, msg).await;
+
+    let reply = if args.current() == Some("off") {
+        server_data.settings.archive_channel = None;
+        tr(&language, "settings.archive_off", &[])
+    } else {
+        let channel = args.single::<ChannelId>()?;
+        let format = match args.single::<String>() {
+            Ok(format) if format.eq_ignore_ascii_case("html") => ArchiveFormat::Html,
+            _ => ArchiveFormat::Text
+        };
+
+        server_data.settings.archive_channel = Some(channel.0 as i64);
+        server_data.settings.archive_format = format;
+        tr(&language, "settings.archive_on", &[("channel", &channel.mention())])
+    };
+
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
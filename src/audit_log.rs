@@ -0,0 +1,144 @@
+use chrono::Utc;
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use tracing::error;
+use crate::db::DatabaseServer;
+use crate::i18n::{self, tr};
+use crate::metrics;
+use crate::party_groups::Group;
+
+// Something that happened to a party, worth telling the moderators about.
+pub(crate) enum PartyEvent {
+    Created,
+    Joined(UserId),
+    Left(UserId),
+    // The owner took the member out of the party.
+    Kicked(UserId),
+    // The member left the server or got banned, and was taken out of the party.
+    Removed { member: UserId, banned: bool },
+    OwnershipTransferred { from: UserId, to: UserId },
+    // The owner changed the party's `field` to `value`, or cleared it.
+    Edited { field: &'static str, value: Option<String> },
+    Expired,
+    Disbanded
+}
+
+impl PartyEvent {
+    fn title_key(&self) -> &'static str {
+        match self {
+            PartyEvent::Created => "audit.created",
+            PartyEvent::Joined(_) => "audit.joined",
+            PartyEvent::Left(_) => "audit.left",
+            PartyEvent::Kicked(_) => "audit.kicked",
+            PartyEvent::Removed { banned: false, .. } => "audit.removed_left",
+            PartyEvent::Removed { banned: true, .. } => "audit.removed_banned",
+            PartyEvent::OwnershipTransferred { .. } => "audit.ownership_transferred",
+            PartyEvent::Edited { .. } => "audit.edited",
+            PartyEvent::Expired => "audit.expired",
+            PartyEvent::Disbanded => "audit.disbanded"
+        }
+    }
+
+    fn colour(&self) -> Colour {
+        match self {
+            PartyEvent::Created => Colour::DARK_GOLD,
+            PartyEvent::Joined(_) => Colour::DARK_GREEN,
+            PartyEvent::Left(_) | PartyEvent::Kicked(_) | PartyEvent::Removed { .. } => Colour::ORANGE,
+            PartyEvent::OwnershipTransferred { .. } | PartyEvent::Edited { .. } => Colour::BLUE,
+            PartyEvent::Expired | PartyEvent::Disbanded => Colour::RED
+        }
+    }
+}
+
+// Posts the event to the guild's log channel, if it has one. `actor` is whoever caused the event,
+// or `None` when the bot did it on its own. Failing to log never fails whatever is being logged.
+pub(crate) async fn log(
+    ctx: &Context,
+    guild: GuildId,
+    group: &Group,
+    actor: Option<UserId>,
+    event: PartyEvent
+) {
+    let settings = match DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await {
+        Ok(server_data) => server_data.settings,
+        Err(why) => {
+            error!(guild = %guild, error = ?why, "Could not read the log channel");
+            return
+        }
+    };
+    let log_channel = match settings.log_channel {
+        Some(channel) => ChannelId(channel as u64),
+        None => return
+    };
+
+    let language = i18n::guild_language(&settings);
+    let result = log_channel.send_message(&ctx.http, |cm| {
+        cm.embed(|ce| {
+            ce.title(tr(language, event.title_key(), &[]));
+            ce.field(tr(language, "audit.party", &[]), format!("{} ({})", group.title(), group.game()), true);
+            ce.field(tr(language, "audit.role", &[]), RoleId(group.role_id as u64).mention(), true);
+            ce.field(tr(language, "audit.actor", &[]), match actor {
+                Some(actor) => actor.mention(),
+                None => tr(language, "audit.bot", &[])
+            }, true);
+
+            match &event {
+                PartyEvent::Joined(member)
+                | PartyEvent::Left(member)
+                | PartyEvent::Kicked(member)
+                | PartyEvent::Removed { member, .. } => {
+                    ce.field(tr(language, "audit.member", &[]), member.mention(), true);
+                },
+                PartyEvent::OwnershipTransferred { from, to } => {
+                    ce.field(tr(language, "audit.from", &[]), from.mention(), true);
+                    ce.field(tr(language, "audit.to", &[]), to.mention(), true);
+                },
+                PartyEvent::Edited { field, value } => {
+                    let field = tr(language, &format!("audit.edited_{}", field), &[]);
+                    let value = value.clone().unwrap_or_else(|| tr(language, "audit.cleared", &[]));
+                    ce.field(field, value, true);
+                },
+                _ => {}
+            }
+
+            ce.colour(event.colour());
+            ce.timestamp(&Utc::now());
+            ce
+        });
+        cm
+    }).await;
+
+    if let Err(why) = result {
+        metrics::record_error(&why);
+        error!(guild = %guild, error = ?why, "Could not post to the log channel");
+    }
+}
+
+#[command]
+#[aliases(log)]
+#[description = "Posts party events (created, joined, left, kicked, edited, expired, ...) in a channel, or turns it off."]
+#[usage = "<#channel> | off"]
+#[example = "#party-log"]
+async fn logchannel(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let language = i18n::language_for(ctx, msg).await;
+
+    let reply = if args.current() == Some("off") {
+        server_data.settings.log_channel = None;
+        tr(&language, "settings.log_off", &[])
+    } else {
+        let channel = args.single::<ChannelId>()?;
+        server_data.settings.log_channel = Some(channel.0 as i64);
+        tr(&language, "settings.log_on", &[("channel", &channel.mention())])
+    };
+
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
@@ -0,0 +1,347 @@
+use std::{env, fmt, fs, io, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use serde::Deserialize;
+use serenity::prelude::TypeMapKey;
+use tracing_subscriber::EnvFilter;
+use crate::party_timer::TimerConfig;
+
+// Where the config file is looked for, unless `HR_BOT_CONFIG` points somewhere else.
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+// Everything the bot needs to know at startup. It is read once from the TOML file, if there is
+// one, then every setting can be overridden by its environment variable.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    // DISCORD_TOKEN
+    pub discord_token: String,
+    // MONGO_URL
+    pub mongo_url: String,
+    // MONGO_DB
+    pub mongo_db: String,
+    // MONGO_COLLECTION
+    pub collection: String,
+    // BOT_PREFIX
+    pub prefix: String,
+    // BOT_OWNERS, comma separated. Added to the owners of the Discord application.
+    pub owners: Vec<u64>,
+    // RUST_LOG or LOG_LEVEL
+    pub log_level: String,
+    // LOG_FORMAT, either "text" or "json".
+    pub log_format: String,
+    // HTTP_ADDR
+    pub http_addr: String,
+    pub party: PartyConfig,
+    pub shards: ShardConfig,
+    pub presence: PresenceConfig
+}
+
+// Defaults for every party. Overridden by the `PARTY_*` environment variables.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PartyConfig {
+    pub min_players: u32,
+    pub max_players: u32,
+    pub tick_secs: u64,
+    pub lonely_mins: u64,
+    pub idle_mins: u64,
+    pub expiry_mins: u64,
+    // How long a member goes without another DM about a new party.
+    pub notify_cooldown_mins: u64,
+    // How long a game's role goes without another ping for a new party.
+    pub ping_cooldown_mins: u64,
+    // How long someone waits in the `lfg` queue before they get taken out of it.
+    pub lfg_timeout_mins: u64
+}
+
+// Which shards this process runs. Leaving `total` unset lets Discord pick the shard count, and
+// runs every shard. Setting `first` and `last` as well splits the shards between processes.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ShardConfig {
+    // SHARD_TOTAL
+    pub total: Option<u64>,
+    // SHARD_FIRST
+    pub first: Option<u64>,
+    // SHARD_LAST
+    pub last: Option<u64>
+}
+
+// The statuses the bot rotates through. `{parties}`, `{guilds}` and `{prefix}` get filled in.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PresenceConfig {
+    // BOT_ACTIVITY sets a single status.
+    pub activities: Vec<String>,
+    // PRESENCE_ROTATE_SECS
+    pub rotate_secs: u64
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            activities: vec![
+                "Makin' Parties!".to_string(),
+                "{parties} parties running across {guilds} servers".to_string()
+            ],
+            rotate_secs: 300
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            discord_token: String::new(),
+            mongo_url: String::new(),
+            mongo_db: String::new(),
+            collection: "Servers".to_string(),
+            prefix: ">?".to_string(),
+            owners: Vec::new(),
+            log_level: "info".to_string(),
+            log_format: "text".to_string(),
+            http_addr: "127.0.0.1:9000".to_string(),
+            party: PartyConfig::default(),
+            shards: ShardConfig::default(),
+            presence: PresenceConfig::default()
+        }
+    }
+}
+
+impl Default for PartyConfig {
+    fn default() -> Self {
+        Self {
+            min_players: 2,
+            max_players: 20,
+            tick_secs: 30,
+            lonely_mins: 1,
+            idle_mins: 15,
+            expiry_mins: 1,
+            notify_cooldown_mins: 30,
+            ping_cooldown_mins: 10,
+            lfg_timeout_mins: 30
+        }
+    }
+}
+
+impl TypeMapKey for Config {
+    type Value = Arc<Config>;
+}
+
+#[derive(Debug)]
+pub(crate) enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Missing(&'static str),
+    Invalid(&'static str, String)
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, why) => write!(f, "Could not read {}: {}", path.display(), why),
+            ConfigError::Parse(path, why) => write!(f, "Could not parse {}: {}", path.display(), why),
+            ConfigError::Missing(key) => write!(
+                f,
+                "`{}` is not set, either in the config file or as {}",
+                key,
+                key.to_uppercase()
+            ),
+            ConfigError::Invalid(key, why) => write!(f, "`{}` is invalid: {}", key, why)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub(crate) fn load() -> Result<Self, ConfigError> {
+        let (path, required) = match env::var("HR_BOT_CONFIG") {
+            Ok(path) => (PathBuf::from(path), true),
+            Err(_) => (PathBuf::from(DEFAULT_CONFIG_PATH), false)
+        };
+
+        // The default config file is optional, one that was asked for isn't.
+        let mut config = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|why| ConfigError::Parse(path.clone(), why))?,
+            Err(why) if why.kind() == io::ErrorKind::NotFound && !required => Config::default(),
+            Err(why) => return Err(ConfigError::Read(path, why))
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        override_string(&mut self.discord_token, "DISCORD_TOKEN");
+        override_string(&mut self.mongo_url, "MONGO_URL");
+        override_string(&mut self.mongo_db, "MONGO_DB");
+        override_string(&mut self.collection, "MONGO_COLLECTION");
+        override_string(&mut self.prefix, "BOT_PREFIX");
+        if let Ok(activity) = env::var("BOT_ACTIVITY") {
+            self.presence.activities = vec![activity];
+        }
+        override_string(&mut self.log_level, "LOG_LEVEL");
+        override_string(&mut self.log_level, "RUST_LOG");
+        override_string(&mut self.log_format, "LOG_FORMAT");
+        override_string(&mut self.http_addr, "HTTP_ADDR");
+
+        if let Ok(owners) = env::var("BOT_OWNERS") {
+            self.owners = owners
+                .split(',')
+                .map(str::trim)
+                .filter(|owner| !owner.is_empty())
+                .map(|owner| owner.parse().map_err(|_| {
+                    ConfigError::Invalid("owners", format!("`{}` is not a user id", owner))
+                }))
+                .collect::<Result<_, _>>()?;
+        }
+
+        override_parsed(&mut self.party.min_players, "party.min_players", "PARTY_MIN_PLAYERS")?;
+        override_parsed(&mut self.party.max_players, "party.max_players", "PARTY_MAX_PLAYERS")?;
+        override_parsed(&mut self.party.tick_secs, "party.tick_secs", "PARTY_TICK_SECS")?;
+        override_parsed(&mut self.party.lonely_mins, "party.lonely_mins", "PARTY_LONELY_MINS")?;
+        override_parsed(&mut self.party.idle_mins, "party.idle_mins", "PARTY_IDLE_MINS")?;
+        override_parsed(&mut self.party.expiry_mins, "party.expiry_mins", "PARTY_EXPIRY_MINS")?;
+        override_parsed(
+            &mut self.party.notify_cooldown_mins,
+            "party.notify_cooldown_mins",
+            "PARTY_NOTIFY_COOLDOWN_MINS"
+        )?;
+        override_parsed(
+            &mut self.party.ping_cooldown_mins,
+            "party.ping_cooldown_mins",
+            "PARTY_PING_COOLDOWN_MINS"
+        )?;
+        override_parsed(&mut self.party.lfg_timeout_mins, "party.lfg_timeout_mins", "PARTY_LFG_TIMEOUT_MINS")?;
+        override_parsed(&mut self.presence.rotate_secs, "presence.rotate_secs", "PRESENCE_ROTATE_SECS")?;
+        override_optional(&mut self.shards.total, "shards.total", "SHARD_TOTAL")?;
+        override_optional(&mut self.shards.first, "shards.first", "SHARD_FIRST")?;
+        override_optional(&mut self.shards.last, "shards.last", "SHARD_LAST")?;
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.discord_token.is_empty() {
+            return Err(ConfigError::Missing("discord_token"))
+        }
+        if self.mongo_url.is_empty() {
+            return Err(ConfigError::Missing("mongo_url"))
+        }
+        if self.mongo_db.is_empty() {
+            return Err(ConfigError::Missing("mongo_db"))
+        }
+        if self.collection.is_empty() {
+            return Err(ConfigError::Invalid("collection", "can't be empty".to_string()))
+        }
+        if self.prefix.is_empty() || self.prefix.contains(char::is_whitespace) {
+            return Err(ConfigError::Invalid("prefix", "has to be a single word".to_string()))
+        }
+        if self.log_format != "text" && self.log_format != "json" {
+            return Err(ConfigError::Invalid("log_format", "has to be \"text\" or \"json\"".to_string()))
+        }
+        if let Err(why) = EnvFilter::try_new(&self.log_level) {
+            return Err(ConfigError::Invalid("log_level", why.to_string()))
+        }
+        if let Err(why) = self.http_addr.parse::<SocketAddr>() {
+            return Err(ConfigError::Invalid("http_addr", why.to_string()))
+        }
+        if self.party.min_players < 2 {
+            return Err(ConfigError::Invalid("party.min_players", "has to be at least 2".to_string()))
+        }
+        if self.party.max_players < self.party.min_players {
+            return Err(ConfigError::Invalid(
+                "party.max_players",
+                "can't be less than party.min_players".to_string()
+            ))
+        }
+        if self.party.tick_secs == 0 {
+            return Err(ConfigError::Invalid("party.tick_secs", "can't be 0".to_string()))
+        }
+        if self.party.lfg_timeout_mins == 0 {
+            return Err(ConfigError::Invalid("party.lfg_timeout_mins", "can't be 0".to_string()))
+        }
+
+        if self.presence.activities.is_empty() {
+            return Err(ConfigError::Invalid("presence.activities", "needs at least one status".to_string()))
+        }
+        if self.presence.rotate_secs == 0 {
+            return Err(ConfigError::Invalid("presence.rotate_secs", "can't be 0".to_string()))
+        }
+
+        match (self.shards.total, self.shards.first, self.shards.last) {
+            (Some(0), _, _) => {
+                return Err(ConfigError::Invalid("shards.total", "can't be 0".to_string()))
+            },
+            (None, Some(_), _) | (None, _, Some(_)) => {
+                return Err(ConfigError::Invalid(
+                    "shards",
+                    "`first` and `last` need `total` to be set".to_string()
+                ))
+            },
+            (Some(_), Some(_), None) | (Some(_), None, Some(_)) => {
+                return Err(ConfigError::Invalid(
+                    "shards",
+                    "`first` and `last` have to be set together".to_string()
+                ))
+            },
+            (Some(total), Some(first), Some(last)) if first > last || last >= total => {
+                return Err(ConfigError::Invalid(
+                    "shards",
+                    format!("{}..={} is not a range of the {} shards", first, last, total)
+                ))
+            },
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn timers(&self) -> TimerConfig {
+        TimerConfig {
+            tick: Duration::from_secs(self.party.tick_secs),
+            min_players: self.party.min_players as usize,
+            lonely_timeout: Duration::from_secs(self.party.lonely_mins * 60),
+            idle_timeout: Duration::from_secs(self.party.idle_mins * 60),
+            expiry: Duration::from_secs(self.party.expiry_mins * 60)
+        }
+    }
+}
+
+fn override_string(value: &mut String, var: &str) {
+    if let Ok(env_value) = env::var(var) {
+        *value = env_value;
+    }
+}
+
+fn override_optional<T: std::str::FromStr>(
+    value: &mut Option<T>,
+    key: &'static str,
+    var: &str
+) -> Result<(), ConfigError> {
+    if let Ok(env_value) = env::var(var) {
+        *value = Some(env_value.parse().map_err(|_| {
+            ConfigError::Invalid(key, format!("{}=`{}` is not a valid number", var, env_value))
+        })?);
+    }
+
+    Ok(())
+}
+
+fn override_parsed<T: std::str::FromStr>(
+    value: &mut T,
+    key: &'static str,
+    var: &str
+) -> Result<(), ConfigError> {
+    if let Ok(env_value) = env::var(var) {
+        *value = env_value.parse().map_err(|_| {
+            ConfigError::Invalid(key, format!("{}=`{}` is not a valid number", var, env_value))
+        })?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,222 @@
+use chrono::Utc;
+use serenity::{framework::standard::Args, model::id::UserId, utils::parse_username};
+use crate::error::PartyError;
+use crate::slots::{self, Slot};
+
+// A party can be planned at most this far ahead.
+const MAX_START_MINS: i64 = 24 * 60;
+
+// What `party create` got asked for. The game can be quoted to span several words, and the flags
+// can go anywhere after the player count, e.g.
+// `4 "League of Legends" Ranked grind --mode flex --start 1h30m --private @friend`
+// The player count can be left out for games with a default size in the server's catalog.
+#[derive(Debug)]
+pub(crate) struct CreateArgs {
+    pub players: Option<u32>,
+    pub game: String,
+    pub title: String,
+    pub mode: Option<String>,
+    // Unix timestamp of when the party plans to start.
+    pub starts_at: Option<i64>,
+    // Only the owner and the members mentioned right after `--private` can join a private party.
+    pub private: bool,
+    pub invited: Vec<UserId>,
+    // Replaces the slots the game has in the catalog, if any.
+    pub slots: Option<Vec<Slot>>
+}
+
+// `max_players` is the most a party can have, which no slot can be bigger than.
+pub(crate) fn parse(mut args: Args, max_players: u32) -> Result<CreateArgs, PartyError> {
+    let mut positional = Vec::new();
+    // A quoted first word is always the game, even one like "7 Days to Die".
+    let mut first_quoted = false;
+    let mut mode = None;
+    let mut starts_at = None;
+    let mut private = false;
+    let mut invited = Vec::new();
+    let mut party_slots = None;
+
+    while !args.is_empty() {
+        let quoted = args.current().map_or(false, |arg| arg.starts_with('"'));
+        let token = match args.single_quoted::<String>() {
+            Ok(token) => token,
+            Err(_) => break
+        };
+
+        match token.as_str() {
+            "--mode" => mode = Some(flag_value(&mut args, "--mode")?),
+            "--start" => starts_at = Some(parse_start(&flag_value(&mut args, "--start")?)?),
+            "--private" => {
+                private = true;
+                while let Some(id) = args.current().and_then(parse_username) {
+                    invited.push(UserId(id));
+                    args.advance();
+                }
+            },
+            "--slots" => party_slots = Some(slots::parse(&flag_value(&mut args, "--slots")?, max_players)?),
+            // A bare `--` is just part of the title.
+            flag if flag.starts_with("--") && flag != "--" => {
+                return Err(PartyError::UnknownFlag(flag.to_string()))
+            },
+            _ => {
+                if positional.is_empty() {
+                    first_quoted = quoted;
+                }
+                positional.push(token)
+            }
+        }
+    }
+
+    let mut positional = positional.into_iter().peekable();
+    let players = match positional.peek() {
+        Some(players) if !first_quoted && looks_numeric(players) => Some(parse_players(&positional.next().unwrap())?),
+        Some(_) => None,
+        None => return Err(PartyError::NoPlayers)
+    };
+    let game = positional.next().ok_or(PartyError::NoGame)?;
+    let title = positional.collect::<Vec<String>>().join(" ");
+    if title.is_empty() {
+        return Err(PartyError::NoTitle)
+    }
+
+    Ok(CreateArgs {
+        players,
+        game,
+        title,
+        mode,
+        starts_at,
+        private,
+        invited,
+        slots: party_slots
+    })
+}
+
+// Whether the argument was meant as a player count, even a wrong one like "-5" or "2.9", rather
+// than the name of a game.
+// Games that only start with a number, like "7th", are still games.
+fn looks_numeric(value: &str) -> bool {
+    let number = value.strip_prefix(|c| c == '-' || c == '+').unwrap_or(value);
+    number.contains(|c: char| c.is_ascii_digit())
+        && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+// Anything that isn't a plain whole number, like "abc", "2.9" or "-5", gets turned down rather
+// than rounded.
+fn parse_players(players: &str) -> Result<u32, PartyError> {
+    players.parse().map_err(|_| PartyError::InvalidPlayers(players.to_string()))
+}
+
+fn flag_value(args: &mut Args, flag: &'static str) -> Result<String, PartyError> {
+    match args.single_quoted::<String>() {
+        Ok(value) if !value.starts_with("--") => Ok(value),
+        _ => Err(PartyError::MissingFlagValue(flag))
+    }
+}
+
+// `--start` takes how long until the party starts, like `45m`, `2h` or `1h30m`.
+fn parse_start(value: &str) -> Result<i64, PartyError> {
+    let invalid = || PartyError::InvalidStart(value.to_string());
+
+    let mut minutes: i64 = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue
+        }
+
+        let amount = i64::from(number.parse::<u32>().map_err(|_| invalid())?);
+        number.clear();
+        minutes += match c {
+            'h' => amount * 60,
+            'm' => amount,
+            _ => return Err(invalid())
+        };
+    }
+
+    if !number.is_empty() || minutes == 0 || minutes > MAX_START_MINS {
+        return Err(invalid())
+    }
+
+    Ok(Utc::now().timestamp() + minutes * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serenity::framework::standard::Delimiter;
+
+    fn parse_str(message: &str) -> Result<CreateArgs, PartyError> {
+        parse(Args::new(message, &[Delimiter::Single(' ')]), 20)
+    }
+
+    #[test]
+    fn a_word_instead_of_a_count_is_the_game() {
+        let args = parse_str("abc Ranked grind").unwrap();
+        assert_eq!(args.players, None);
+        assert_eq!(args.game, "abc");
+        assert_eq!(args.title, "Ranked grind");
+    }
+
+    #[test]
+    fn a_fractional_count_is_turned_down() {
+        let result = parse_str("2.9 Chess Casual");
+        assert!(matches!(result, Err(PartyError::InvalidPlayers(players)) if players == "2.9"));
+    }
+
+    #[test]
+    fn a_negative_count_is_turned_down() {
+        let result = parse_str("-5 Chess Casual");
+        assert!(matches!(result, Err(PartyError::InvalidPlayers(players)) if players == "-5"));
+    }
+
+    #[test]
+    fn a_quoted_game_can_span_several_words() {
+        let args = parse_str("5 \"League of Legends\" Ranked grind").unwrap();
+        assert_eq!(args.players, Some(5));
+        assert_eq!(args.game, "League of Legends");
+        assert_eq!(args.title, "Ranked grind");
+    }
+
+    #[test]
+    fn a_game_starting_with_a_number_is_not_a_count() {
+        let args = parse_str("4 7th Guest night").unwrap();
+        assert_eq!(args.players, Some(4));
+        assert_eq!(args.game, "7th");
+
+        let args = parse_str("7th Guest night").unwrap();
+        assert_eq!(args.players, None);
+        assert_eq!(args.game, "7th");
+    }
+
+    #[test]
+    fn a_quoted_game_is_never_a_count() {
+        let args = parse_str("\"7 Days to Die\" Survival").unwrap();
+        assert_eq!(args.players, None);
+        assert_eq!(args.game, "7 Days to Die");
+
+        let args = parse_str("\"2048\" Speedrun").unwrap();
+        assert_eq!(args.players, None);
+        assert_eq!(args.game, "2048");
+    }
+
+    #[test]
+    fn a_slot_cant_be_bigger_than_a_party() {
+        assert!(matches!(parse_str("WoW Raid night --slots dps=21"), Err(PartyError::InvalidSlots(_))));
+        assert!(parse_str("WoW Raid night --slots dps=20").is_ok());
+    }
+
+    #[test]
+    fn only_mentions_after_private_are_invited() {
+        let args = parse_str("4 Chess Rematch with <@123> --private <@456> <@!789> tonight").unwrap();
+        assert!(args.private);
+        assert_eq!(args.invited, vec![UserId(456), UserId(789)]);
+        assert_eq!(args.title, "Rematch with <@123> tonight");
+    }
+
+    #[test]
+    fn a_bare_double_dash_stays_in_the_title() {
+        let args = parse_str("4 Chess Casual -- anyone welcome").unwrap();
+        assert_eq!(args.title, "Casual -- anyone welcome");
+    }
+}
@@ -1,12 +1,17 @@
-use std::env;
+use std::collections::HashMap;
 
 use serenity::prelude::TypeMapKey;
 use mongodb::{Collection, Client};
-use mongodb::bson::{doc, Document};
+use mongodb::bson::{doc, Bson, Document};
 use serde::{Serialize, Deserialize};
-use serenity::prelude::Context;
-use mongodb::options::FindOneAndReplaceOptions;
+use serenity::{futures::StreamExt, prelude::Context};
+use mongodb::options::{FindOneAndReplaceOptions, UpdateOptions};
+use tracing::warn;
+use crate::config::Config;
+use crate::games::GameEntry;
+use crate::metrics::DB_LATENCY;
 use crate::party_groups::Group;
+use crate::subscriptions::Subscriber;
 
 pub struct Database;
 
@@ -18,70 +23,194 @@ pub(crate) struct DatabaseServer {
     _id: i64,
     owner_id: i64,
     pub parties: Vec<Group>,
+    #[serde(default)]
+    pub settings: ServerSettings,
+    // The most recent parties that ended, for the `stats` command.
+    #[serde(default)]
+    pub party_history: Vec<PartyRecord>,
+}
+
+// How many times each command has been run in a guild. They live in a collection of their own,
+// because the guild's document gets written back whole by `insert_or_replace`, which would undo
+// every count made since it was read.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CommandCounts {
+    #[serde(default)]
+    counts: HashMap<String, i64>
+}
+
+// Past this, the oldest records get dropped so the document doesn't keep growing.
+const MAX_PARTY_HISTORY: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+// What is left of a party after it ends.
+pub(crate) struct PartyRecord {
+    pub created_at: i64,
+    pub game: String,
+    pub peak_players: i64,
+    pub max_players: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+// Per-guild settings, changed through the `settings` commands.
+pub(crate) struct ServerSettings {
+    // Where transcripts of ended parties get posted. Archiving is off while this is unset.
+    pub archive_channel: Option<i64>,
+    #[serde(default)]
+    pub archive_format: ArchiveFormat,
+    // Where party events get logged for the moderators. Logging is off while this is unset.
+    #[serde(default)]
+    pub log_channel: Option<i64>,
+    // Used instead of the default prefix when set.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    // Besides administrators, members with any of these roles can create parties.
+    #[serde(default)]
+    pub create_roles: Vec<i64>,
+    // The language the bot speaks in the guild. English while this is unset.
+    #[serde(default)]
+    pub language: Option<String>,
+    // Members that picked their own language, by their id.
+    #[serde(default)]
+    pub member_languages: HashMap<String, String>,
+    // How party announcements look.
+    #[serde(default)]
+    pub embed: EmbedTemplate,
+    // The games parties get made for, so everyone calls them the same.
+    #[serde(default)]
+    pub games: Vec<GameEntry>,
+    // Who wants to hear about new parties, by member id.
+    #[serde(default)]
+    pub subscribers: HashMap<String, Subscriber>,
+    // Each member's rating in the games they set one for, by member id and then game.
+    #[serde(default)]
+    pub ratings: HashMap<String, HashMap<String, u32>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+// Changed through `settings embed`.
+pub(crate) struct EmbedTemplate {
+    // DARK_GOLD while this is unset.
+    pub colour: Option<u32>,
+    pub show_game: bool,
+    pub show_slots: bool,
+    pub show_start: bool,
+    // Mentions the owner instead of just naming them.
+    pub mention_owner: bool,
+    // Shown on the announcements of parties for the game, by the game's name in lowercase.
+    pub game_images: HashMap<String, String>,
+    pub footer: Option<String>,
+}
+
+impl Default for EmbedTemplate {
+    fn default() -> Self {
+        Self {
+            colour: None,
+            show_game: true,
+            show_slots: true,
+            show_start: true,
+            mention_owner: false,
+            game_images: HashMap::new(),
+            footer: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum ArchiveFormat {
+    Text,
+    Html
+}
+
+impl Default for ArchiveFormat {
+    fn default() -> Self {
+        ArchiveFormat::Text
+    }
 }
 
 impl TypeMapKey for Database {
     type Value = Client;
 }
 
+// Asks the database to answer, without touching any document.
+pub(crate) async fn ping(client: &Client, mongo_database: &str) -> mongodb::error::Result<Document> {
+    let _timer = DB_LATENCY.with_label_values(&["ping"]).start_timer();
+    client.database(mongo_database).run_command(doc! { "ping": 1 }, None).await
+}
+
 impl DatabaseServer {
     pub(crate) async fn get_or_insert_new(
         ctx: &Context,
         _id: i64,
         owner_id: Option<i64>
-    ) -> DatabaseServer {
-        let get_result = DatabaseServer::get(ctx, _id).await;
+    ) -> mongodb::error::Result<DatabaseServer> {
+        let get_result = DatabaseServer::get(ctx, _id).await?;
 
-        if get_result == None && owner_id.is_some() {
-            bson::from_document(
+        let document = match (get_result, owner_id) {
+            (Some(document), _) => document,
+            (None, Some(owner_id)) => {
                 DatabaseServer::insert_or_replace(ctx, DatabaseServer {
                     _id,
-                    owner_id: owner_id.unwrap(),
-                    parties: Vec::new()
-                }).await
-            ).unwrap()
-        } else {
-            bson::from_document(get_result.unwrap()).unwrap()
-        }
+                    owner_id,
+                    parties: Vec::new(),
+                    settings: ServerSettings::default(),
+                    party_history: Vec::new()
+                }).await?
+            },
+            // Every guild gets its document when the bot joins it, see `guild_create`.
+            (None, None) => panic!("Guild {} is not in the database", _id)
+        };
+
+        Ok(bson::from_document(document)?)
     }
 
-    pub(crate) async fn get(ctx: &Context, _id: i64) -> Option<Document> {
+    pub(crate) async fn get(ctx: &Context, _id: i64) -> mongodb::error::Result<Option<Document>> {
         let document_id = doc! { "_id": _id };
-        let document = DatabaseServer::get_collection(ctx)
+        let _timer = DB_LATENCY.with_label_values(&["get"]).start_timer();
+        DatabaseServer::get_collection(ctx)
             .await
             .find_one(document_id, None)
             .await
-            .unwrap();
+    }
 
-        document
+    pub(crate) async fn all(ctx: &Context) -> mongodb::error::Result<Vec<DatabaseServer>> {
+        let _timer = DB_LATENCY.with_label_values(&["all"]).start_timer();
+        let mut cursor = DatabaseServer::get_collection(ctx).await.find(None, None).await?;
+        let mut servers = Vec::new();
+
+        while let Some(document) = cursor.next().await {
+            match bson::from_document(document?) {
+                Ok(server) => servers.push(server),
+                Err(why) => warn!(error = ?why, "Skipping a server that could not be read")
+            }
+        }
+
+        Ok(servers)
     }
 
     pub(crate) async fn insert_or_replace(
         ctx: &Context,
         database_guild: DatabaseServer
-    ) -> Document {
-        let new_document = bson::to_document(&database_guild).unwrap();
+    ) -> mongodb::error::Result<Document> {
+        let new_document = bson::to_document(&database_guild)?;
 
         let mut replace_options = FindOneAndReplaceOptions::default();
         replace_options.upsert = Some(true);
 
         let collection = DatabaseServer::get_collection(ctx).await;
+        let _timer = DB_LATENCY.with_label_values(&["insert_or_replace"]).start_timer();
         // Find and replace the document and return it
-        match collection.find_one_and_replace(
+        let replaced = collection.find_one_and_replace(
             doc! { "_id": database_guild._id },
-            new_document,
+            new_document.clone(),
             replace_options
-        ).await.unwrap() {
-            Some(document) => document,
-            None => {
-                collection.find_one(
-                    doc! { "_id": database_guild._id },
-                    None
-                )
-                .await
-                .unwrap()
-                .unwrap()
-            }
+        ).await?;
+
+        match replaced {
+            Some(document) => Ok(document),
+            // Nothing was there to replace, so it's the document that just got inserted.
+            None => Ok(new_document)
         }
     }
 
@@ -90,34 +219,132 @@ impl DatabaseServer {
         id: i64
     ) -> mongodb::error::Result<Option<Document>> {
         let document_id = doc! { "_id": id };
+        let _timer = DB_LATENCY.with_label_values(&["delete"]).start_timer();
+        DatabaseServer::get_command_counts(ctx).await.delete_one(document_id.clone(), None).await?;
         DatabaseServer::get_collection(ctx).await.find_one_and_delete(document_id, None).await
     }
 
-    pub(crate) async fn get_collection(ctx: &Context) -> Collection {
-        let mongo_database = env::var("MONGO_DB").unwrap();
-        let database = ctx.data
-            .read()
+    // Counts the command in place, so concurrent commands don't overwrite each other's counts.
+    pub(crate) async fn increment_command(
+        ctx: &Context,
+        id: i64,
+        command_name: &str
+    ) -> mongodb::error::Result<()> {
+        let _timer = DB_LATENCY.with_label_values(&["increment_command"]).start_timer();
+        let field = format!("counts.{}", command_name);
+
+        let mut update_options = UpdateOptions::default();
+        update_options.upsert = Some(true);
+
+        DatabaseServer::get_command_counts(ctx)
             .await
+            .update_one(doc! { "_id": id }, doc! { "$inc": { field: 1 } }, update_options)
+            .await?;
+
+        Ok(())
+    }
+
+    // Changes one member's subscriptions in place, so members subscribing at the same time don't
+    // overwrite each other. `None` removes them altogether.
+    pub(crate) async fn set_subscriber(
+        ctx: &Context,
+        id: i64,
+        member: &str,
+        subscriber: Option<&Subscriber>
+    ) -> mongodb::error::Result<()> {
+        let value = subscriber.map(bson::to_bson).transpose()?;
+        DatabaseServer::update_field(ctx, id, format!("settings.subscribers.{}", member), value).await
+    }
+
+    // Changes one rating of one member in place, or removes it with `None`.
+    pub(crate) async fn set_rating(
+        ctx: &Context,
+        id: i64,
+        member: &str,
+        game: &str,
+        rating: Option<u32>
+    ) -> mongodb::error::Result<()> {
+        let field = format!("settings.ratings.{}.{}", member, game);
+        DatabaseServer::update_field(ctx, id, field, rating.map(|rating| Bson::Int64(rating.into()))).await
+    }
+
+    // Sets a single field of the guild's document, or removes it with `None`.
+    async fn update_field(
+        ctx: &Context,
+        id: i64,
+        field: String,
+        value: Option<Bson>
+    ) -> mongodb::error::Result<()> {
+        let update = match value {
+            Some(value) => doc! { "$set": { field: value } },
+            None => doc! { "$unset": { field: "" } }
+        };
+
+        let _timer = DB_LATENCY.with_label_values(&["update_field"]).start_timer();
+        DatabaseServer::get_collection(ctx)
+            .await
+            .update_one(doc! { "_id": id }, update, None)
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn command_counts(
+        ctx: &Context,
+        id: i64
+    ) -> mongodb::error::Result<HashMap<String, i64>> {
+        let _timer = DB_LATENCY.with_label_values(&["command_counts"]).start_timer();
+        let document = DatabaseServer::get_command_counts(ctx)
+            .await
+            .find_one(doc! { "_id": id }, None)
+            .await?;
+
+        Ok(document
+            .and_then(|document| bson::from_document::<CommandCounts>(document).ok())
+            .unwrap_or_default()
+            .counts)
+    }
+
+    pub(crate) async fn get_collection(ctx: &Context) -> Collection {
+        let data = ctx.data.read().await;
+        let config = data.get::<Config>().unwrap();
+        let database = data
             .get::<Database>()
             .unwrap()
-            .database(&mongo_database);
+            .database(&config.mongo_db);
 
-        database.collection("Servers")
+        database.collection(&config.collection)
     }
 
-    pub(crate) async fn party_owner(ctx: &Context, _id: i64, party_owner_id: i64) -> bool {
-        let dbs = DatabaseServer::get_or_insert_new(ctx, _id, None).await;
-        for party in dbs.parties { if party.owner == party_owner_id { return true } }
-        false
+    // Next to the guilds' collection, e.g. "Servers_command_counts".
+    async fn get_command_counts(ctx: &Context) -> Collection {
+        let data = ctx.data.read().await;
+        let config = data.get::<Config>().unwrap();
+        let database = data
+            .get::<Database>()
+            .unwrap()
+            .database(&config.mongo_db);
+
+        database.collection(&format!("{}_command_counts", config.collection))
+    }
+
+    pub(crate) async fn party_owner(
+        ctx: &Context,
+        _id: i64,
+        party_owner_id: i64
+    ) -> mongodb::error::Result<bool> {
+        let dbs = DatabaseServer::get_or_insert_new(ctx, _id, None).await?;
+        Ok(dbs.parties.iter().any(|party| party.owner == party_owner_id))
     }
 
     pub(crate) async fn add_party(&mut self, group: Group) {
         self.parties.push(group);
     }
 
-    pub(crate) async fn edit_party(&mut self, owner: &i64, party_group: Group) {
+    // Parties are looked up by their role, since the owner of a party can change.
+    pub(crate) async fn edit_party(&mut self, role_id: &i64, party_group: Group) {
         for (i, party) in self.parties.iter().enumerate() {
-            if &party.owner == owner {
+            if &party.role_id == role_id {
                 self.parties.remove(i);
                 self.parties.push(party_group);
                 break
@@ -125,9 +352,9 @@ impl DatabaseServer {
         }
     }
 
-    pub(crate) async fn get_party(&self, owner: &i64) -> Option<Group> {
+    pub(crate) async fn get_party(&self, role_id: &i64) -> Option<Group> {
         for party in self.parties.iter() {
-            if &party.owner == owner {
+            if &party.role_id == role_id {
                 return Some(party.clone())
             }
         }
@@ -135,12 +362,30 @@ impl DatabaseServer {
         None
     }
 
-    pub(crate) async fn delete_party(&mut self, owner: &i64) {
+    pub(crate) async fn delete_party(&mut self, role_id: &i64) {
         for (i, party) in self.parties.iter().enumerate() {
-            if &party.owner == owner {
+            if &party.role_id == role_id {
                 self.parties.remove(i);
                 break
             }
         }
     }
+
+    pub(crate) fn record_party(&mut self, group: &Group) {
+        self.party_history.push(PartyRecord {
+            created_at: group.created_at,
+            game: group.game().to_string(),
+            peak_players: group.peak_players() as i64,
+            max_players: group.max_player_amount() as i64,
+        });
+
+        if self.party_history.len() > MAX_PARTY_HISTORY {
+            let overflow = self.party_history.len() - MAX_PARTY_HISTORY;
+            self.party_history.drain(..overflow);
+        }
+    }
+
+    pub(crate) fn owners(&self) -> Vec<i64> {
+        self.parties.iter().map(|party| party.owner).collect()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::time::Instant;
+
+use serenity::{
+    client::bridge::gateway::ShardId,
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use crate::config::Config;
+use crate::db::{self, Database, DatabaseServer};
+use crate::health::ShardManagerContainer;
+use crate::i18n::{self, tr};
+
+// When the bot started, for the uptime in `about`.
+pub(crate) struct StartTime;
+
+impl TypeMapKey for StartTime {
+    type Value = Instant;
+}
+
+#[command]
+#[description = "Shows how quickly the bot can reach Discord and its database."]
+async fn ping(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let gateway = {
+        let shard_manager = ctx.data.read().await.get::<ShardManagerContainer>().unwrap().clone();
+        let shard_manager = shard_manager.lock().await;
+        let runners = shard_manager.runners.lock().await;
+        runners.get(&ShardId(ctx.shard_id)).and_then(|runner| runner.latency)
+    };
+
+    let language = i18n::language_for(ctx, msg).await;
+    let rest_start = Instant::now();
+    let mut reply = msg.channel_id.say(&ctx.http, tr(&language, "diagnostics.pinging", &[])).await?;
+    let rest = rest_start.elapsed();
+
+    let (client, mongo_database) = {
+        let data = ctx.data.read().await;
+        (
+            data.get::<Database>().unwrap().clone(),
+            data.get::<Config>().unwrap().mongo_db.clone()
+        )
+    };
+    let db_start = Instant::now();
+    let database = match db::ping(&client, &mongo_database).await {
+        Ok(_) => format!("{} ms", db_start.elapsed().as_millis()),
+        Err(_) => tr(&language, "diagnostics.unreachable", &[])
+    };
+
+    reply.edit(&ctx.http, |em| {
+        em.content(tr(&language, "diagnostics.pong", &[]));
+        em.embed(|ce| {
+            ce.field(tr(&language, "diagnostics.gateway", &[]), match gateway {
+                Some(latency) => format!("{} ms", latency.as_millis()),
+                // The shard hasn't sent a heartbeat yet.
+                None => tr(&language, "diagnostics.unknown", &[])
+            }, true);
+            ce.field(tr(&language, "diagnostics.rest", &[]), format!("{} ms", rest.as_millis()), true);
+            ce.field(tr(&language, "diagnostics.database", &[]), database, true);
+            ce.colour(Colour::DARK_GOLD);
+            ce
+        })
+    }).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Shows what version of the bot is running and how it is doing."]
+async fn about(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let uptime = ctx.data.read().await.get::<StartTime>().unwrap().elapsed().as_secs();
+    // Counted from the database, so it covers every shard even when the bot is split across
+    // processes.
+    let servers = DatabaseServer::all(ctx).await?;
+    let parties: usize = servers.iter().map(|server| server.parties.len()).sum();
+    let language = i18n::language_for(ctx, msg).await;
+
+    msg.channel_id.send_message(&ctx.http, |cm| {
+        cm.embed(|ce| {
+            ce.title("Heroes' Respite Bot");
+            ce.field(tr(&language, "diagnostics.version", &[]), env!("CARGO_PKG_VERSION"), true);
+            ce.field(tr(&language, "diagnostics.uptime", &[]), format!(
+                "{}d {}h {}m",
+                uptime / 86400,
+                uptime % 86400 / 3600,
+                uptime % 3600 / 60
+            ), true);
+            ce.field(tr(&language, "diagnostics.servers", &[]), servers.len(), true);
+            ce.field(tr(&language, "diagnostics.active_parties", &[]), parties, true);
+            ce.colour(Colour::DARK_GOLD);
+            ce
+        });
+        cm
+    }).await?;
+
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use std::time::{Duration, Instant};
+
+use serenity::{
+    builder::{CreateEmbed, CreateEmbedAuthor},
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use crate::db::{DatabaseServer, ServerSettings};
+use crate::games;
+use crate::i18n::{self, tr};
+use crate::party_groups::Group;
+use crate::party_timer;
+
+// The one place party announcements get drawn, for `create` and every update after it, following
+// the guild's template.
+pub(crate) fn party_embed<'a>(
+    ce: &'a mut CreateEmbed,
+    group: &Group,
+    owner: &User,
+    avatar_url: &str,
+    expires_in: Option<Duration>,
+    settings: &ServerSettings
+) -> &'a mut CreateEmbed {
+    let language = i18n::guild_language(settings);
+    let template = &settings.embed;
+
+    let mut author_embed = CreateEmbedAuthor::default();
+    author_embed.icon_url(avatar_url);
+    author_embed.name(group.title());
+    let owner_name = if template.mention_owner { owner.mention() } else { owner.name.clone() };
+    ce.description(tr(language, "party.created_by", &[("owner", &owner_name)]));
+    ce.set_author(author_embed);
+    let game = games::find(&settings.games, group.game());
+    match game.and_then(|game| game.icon_url.as_ref()) {
+        Some(icon_url) => ce.thumbnail(icon_url),
+        None => ce.thumbnail(avatar_url)
+    };
+    ce.colour(template.colour.map_or(Colour::DARK_GOLD, Colour::new));
+
+    if template.show_game {
+        let name = game.map_or_else(|| group.game().to_string(), |game| game.display_name());
+        ce.field(tr(language, "party.game", &[]), name, true);
+    }
+    ce.field(
+        tr(language, "party.players", &[]),
+        group.players().unwrap_or_else(|| tr(language, "party.nobody", &[])),
+        true
+    );
+    if template.show_slots {
+        let open = group.max_player_amount().saturating_sub(group.player_amount());
+        ce.field(
+            tr(language, "party.slots", &[]),
+            tr(language, "party.slots_open", &[("open", &open), ("max", &group.max_player_amount())]),
+            true
+        );
+    }
+
+    for slot in group.slots.iter() {
+        let members: Vec<String> = slot.members
+            .iter()
+            .map(|&member| UserId(member as u64).mention())
+            .collect();
+        ce.field(
+            format!("{} {} ({}/{})", slot.emoji, slot.name, slot.members.len(), slot.quota),
+            if members.is_empty() { tr(language, "party.nobody", &[]) } else { members.join("\n") },
+            true
+        );
+    }
+
+    if let Some(mode) = &group.mode {
+        ce.field(tr(language, "party.mode", &[]), mode, true);
+    }
+    if let (true, Some(starts_at)) = (template.show_start, group.starts_at) {
+        // Discord shows this in everyone's own timezone.
+        ce.field(tr(language, "party.starts", &[]), format!("<t:{}:R>", starts_at), true);
+    }
+    if group.private {
+        ce.field(tr(language, "party.private", &[]), tr(language, "party.invite_only", &[]), true);
+    }
+
+    if let Some(image) = template.game_images.get(&group.game().to_lowercase()) {
+        ce.image(image);
+    }
+    if let Some(footer) = &template.footer {
+        ce.footer(|cf| cf.text(footer));
+    }
+
+    if let Some(expires_in) = expires_in {
+        ce.field(tr(language, "party.closing_in", &[]), format_remaining(expires_in, language), true);
+        ce.colour(Colour::ORANGE);
+    }
+
+    ce
+}
+
+// Re-renders the announcement of a party, including its countdown if it is about to expire.
+pub(crate) async fn update_party_embed(ctx: &Context, guild: GuildId, group: &Group) -> CommandResult {
+    if !group.has_announcement() {
+        return Ok(())
+    }
+
+    let owner = UserId(group.owner as u64).to_user(&ctx.http).await?;
+    let avatar_url = avatar_url(ctx, &owner).await?;
+    let expires_in = party_timer::expires_at(ctx, group.role_id)
+        .await
+        .map(|expires_at| expires_at.saturating_duration_since(Instant::now()));
+    let settings = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?.settings;
+
+    ChannelId(group.channel_id as u64).edit_message(&ctx.http, group.message_id as u64, |em| {
+        em.embed(|ce| party_embed(ce, group, &owner, &avatar_url, expires_in, &settings))
+    }).await?;
+
+    Ok(())
+}
+
+// Falls back to the bot's avatar for users without one.
+pub(crate) async fn avatar_url(ctx: &Context, user: &User) -> serenity::Result<String> {
+    match user.avatar_url() {
+        Some(url) => Ok(url),
+        None => Ok(ctx.http.get_current_user().await?.face())
+    }
+}
+
+pub(crate) fn format_remaining(remaining: Duration, language: &str) -> String {
+    let secs = remaining.as_secs();
+    if secs >= 60 {
+        tr(language, "time.minutes", &[("count", &((secs + 59) / 60))])
+    } else {
+        tr(language, "time.seconds", &[("count", &secs)])
+    }
+}
+
+// Anything longer gets cut off by Discord anyway.
+const MAX_FOOTER_LEN: usize = 2048;
+
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "on" | "yes" | "true" => Some(true),
+        "off" | "no" | "false" => Some(false),
+        _ => None
+    }
+}
+
+// `#1abc9c` or `1abc9c`.
+fn parse_colour(value: &str) -> Option<u32> {
+    u32::from_str_radix(value.trim_start_matches('#'), 16)
+        .ok()
+        .filter(|colour| *colour <= 0xFF_FF_FF)
+}
+
+#[command]
+#[description = "Changes how party announcements look in this server."]
+#[usage = "colour <#hex|default> | game <on|off> | slots <on|off> | start <on|off> | mention <on|off> | image <game> <url|off> | footer <text|off>"]
+#[example = "colour #1abc9c"]
+#[example = "image \"League of Legends\" https://example.com/league.png"]
+#[example = "footer Be nice to each other!"]
+#[min_args(2)]
+async fn embed(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let language = i18n::language_for(ctx, msg).await;
+    let option = args.single::<String>()?.to_lowercase();
+    let template = &mut server_data.settings.embed;
+
+    let valid = match option.as_str() {
+        "colour" | "color" => match args.single::<String>()?.as_str() {
+            "default" => {
+                template.colour = None;
+                true
+            },
+            value => match parse_colour(value) {
+                Some(colour) => {
+                    template.colour = Some(colour);
+                    true
+                },
+                None => false
+            }
+        },
+        "game" | "slots" | "start" | "mention" => match parse_switch(&args.single::<String>()?) {
+            Some(on) => {
+                match option.as_str() {
+                    "game" => template.show_game = on,
+                    "slots" => template.show_slots = on,
+                    "start" => template.show_start = on,
+                    _ => template.mention_owner = on
+                }
+                true
+            },
+            None => false
+        },
+        "image" => {
+            // Parties of catalog games go by the catalog's name, so the image has to as well.
+            let name = args.single_quoted::<String>()?;
+            let game = match games::find(&server_data.settings.games, &name) {
+                Some(game) => game.name.to_lowercase(),
+                None => name.to_lowercase()
+            };
+            match args.single::<String>()?.as_str() {
+                "off" => {
+                    template.game_images.remove(&game);
+                    true
+                },
+                url if url.starts_with("https://") || url.starts_with("http://") => {
+                    template.game_images.insert(game, url.to_string());
+                    true
+                },
+                _ => false
+            }
+        },
+        "footer" => match args.rest() {
+            "off" => {
+                template.footer = None;
+                true
+            },
+            footer if footer.chars().count() <= MAX_FOOTER_LEN => {
+                template.footer = Some(footer.to_string());
+                true
+            },
+            _ => false
+        },
+        _ => {
+            let reply = tr(&language, "settings.embed_unknown_option", &[("option", &option)]);
+            msg.channel_id.say(&ctx.http, reply).await?;
+            return Ok(())
+        }
+    };
+
+    if !valid {
+        let reply = tr(&language, "settings.embed_invalid_value", &[("option", &option)]);
+        msg.channel_id.say(&ctx.http, reply).await?;
+        return Ok(())
+    }
+
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    msg.channel_id.say(&ctx.http, tr(&language, "settings.embed_updated", &[])).await?;
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::{collections::HashMap, error::Error, fmt, time::{Duration, Instant}};
+
+use serenity::{
+    http::error::Error as HttpError,
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use tracing::debug;
+use crate::CREATE_COMMAND;
+use crate::i18n::{DEFAULT_LANGUAGE, tr, tr_or};
+
+// However many commands fail in a channel, only one error gets shown there this often.
+const REPORT_COOLDOWN: Duration = Duration::from_secs(10);
+
+// How long an error stays up before it gets cleaned up.
+const REPORT_LIFETIME: Duration = Duration::from_secs(20);
+
+// Everything that can go wrong with a party command.
+#[derive(Debug)]
+pub(crate) enum PartyError {
+    // What the user typed is wrong.
+    NoPlayers,
+    InvalidPlayers(String),
+    NoGame,
+    NoTitle,
+    UnknownFlag(String),
+    MissingFlagValue(&'static str),
+    InvalidStart(String),
+    InvalidSlots(String),
+    TooManyPeople(u32),
+    TooLittlePeople(u32),
+    // Probably a typo of a game in the catalog.
+    UnknownGame { game: String, suggestions: Vec<String> },
+    // The user isn't allowed to do this.
+    PartyOwner,
+    NotPartyOwner,
+    // Talking to Discord failed, e.g. because the bot is missing a permission.
+    Discord(serenity::Error),
+    Database(mongodb::error::Error)
+}
+
+impl PartyError {
+    // Whether the user can fix this themselves. Anything else is the bot's problem, and worth
+    // logging as such.
+    pub(crate) fn is_user_error(&self) -> bool {
+        !matches!(self, PartyError::Discord(_) | PartyError::Database(_))
+    }
+
+    // The usage of `create`, for the mistakes that come from typing it wrong.
+    pub(crate) fn usage_hint(&self, language: &str) -> Option<String> {
+        match self {
+            PartyError::NoPlayers
+            | PartyError::InvalidPlayers(_)
+            | PartyError::NoGame
+            | PartyError::NoTitle
+            | PartyError::UnknownFlag(_)
+            | PartyError::MissingFlagValue(_)
+            | PartyError::InvalidStart(_)
+            | PartyError::InvalidSlots(_) => {
+                let usage = tr_or(language, "usages.create", CREATE_COMMAND.options.usage)?;
+                Some(tr(language, "error.usage", &[("usage", &format!("party create {}", usage))]))
+            },
+            _ => None
+        }
+    }
+
+    // What to tell the user, in their language.
+    pub(crate) fn message(&self, language: &str) -> String {
+        match self {
+            PartyError::NoPlayers => tr(language, "error.no_players", &[]),
+            PartyError::InvalidPlayers(players) => tr(language, "error.invalid_players", &[("players", players)]),
+            PartyError::NoGame => tr(language, "error.no_game", &[]),
+            PartyError::NoTitle => tr(language, "error.no_title", &[]),
+            PartyError::UnknownFlag(flag) => tr(language, "error.unknown_flag", &[("flag", flag)]),
+            PartyError::MissingFlagValue(flag) => tr(language, "error.missing_flag_value", &[("flag", flag)]),
+            PartyError::InvalidStart(start) => tr(language, "error.invalid_start", &[("start", start)]),
+            PartyError::InvalidSlots(slots) => tr(language, "error.invalid_slots", &[("slots", slots)]),
+            PartyError::TooManyPeople(max) => tr(language, "error.too_many_people", &[("max", max)]),
+            PartyError::TooLittlePeople(min) => tr(language, "error.too_little_people", &[("min", min)]),
+            PartyError::UnknownGame { game, suggestions } => tr(language, "error.unknown_game", &[
+                ("game", game),
+                ("suggestions", &suggestions.join(", "))
+            ]),
+            PartyError::PartyOwner => tr(language, "error.party_owner", &[]),
+            PartyError::NotPartyOwner => tr(language, "error.not_party_owner", &[]),
+            PartyError::Discord(why) if missing_permissions(why) => tr(language, "error.missing_permissions", &[]),
+            PartyError::Discord(_) => tr(language, "error.discord", &[]),
+            PartyError::Database(_) => tr(language, "error.database", &[])
+        }
+    }
+}
+
+impl fmt::Display for PartyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message(DEFAULT_LANGUAGE))
+    }
+}
+
+impl Error for PartyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PartyError::Discord(why) => Some(why),
+            PartyError::Database(why) => Some(why),
+            _ => None
+        }
+    }
+}
+
+impl From<serenity::Error> for PartyError {
+    fn from(why: serenity::Error) -> Self {
+        PartyError::Discord(why)
+    }
+}
+
+impl From<mongodb::error::Error> for PartyError {
+    fn from(why: mongodb::error::Error) -> Self {
+        PartyError::Database(why)
+    }
+}
+
+fn missing_permissions(why: &serenity::Error) -> bool {
+    match why {
+        serenity::Error::Http(why) => match &**why {
+            HttpError::UnsuccessfulRequest(response) => response.status_code.as_u16() == 403,
+            _ => false
+        },
+        _ => false
+    }
+}
+
+// When an error was last shown in each channel.
+pub(crate) struct ErrorReports;
+
+impl TypeMapKey for ErrorReports {
+    type Value = HashMap<ChannelId, Instant>;
+}
+
+// Shows an error in the channel of the message that caused it, unless one was just shown there.
+// Returns whether it was shown.
+pub(crate) async fn report(
+    ctx: &Context,
+    msg: &Message,
+    title: String,
+    description: Option<String>
+) -> serenity::Result<bool> {
+    {
+        let now = Instant::now();
+        let mut data = ctx.data.write().await;
+        let reports = data.get_mut::<ErrorReports>().unwrap();
+        if let Some(last) = reports.get(&msg.channel_id) {
+            if now.duration_since(*last) < REPORT_COOLDOWN {
+                debug!(channel = %msg.channel_id, "Not showing an error, one was just shown");
+                return Ok(false)
+            }
+        }
+
+        reports.retain(|_, last| now.duration_since(*last) < REPORT_COOLDOWN);
+        reports.insert(msg.channel_id, now);
+    }
+
+    let error_msg = msg.channel_id.send_message(&ctx.http, |cm| {
+        cm.embed(|ce| {
+            ce.title(title);
+            if let Some(description) = description {
+                ce.description(description);
+            }
+            ce.colour(Colour::RED);
+            ce
+        });
+        cm
+    }).await?;
+
+    // Shows the error for a while, then cleans it up along with the message that caused it.
+    tokio::time::delay_for(REPORT_LIFETIME).await;
+    error_msg.delete(&ctx.http).await?;
+    msg.delete(&ctx.http).await?;
+
+    Ok(true)
+}
@@ -0,0 +1,358 @@
+use serde::{Serialize, Deserialize};
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use crate::config::Config;
+use crate::db::DatabaseServer;
+use crate::i18n::{self, tr};
+use crate::slots::{self, Slot};
+
+// How many "did you mean" suggestions an unknown game gets.
+const MAX_SUGGESTIONS: usize = 3;
+
+// Shorter names than this can start too many games to be taken as the start of one.
+const MIN_PREFIX: usize = 3;
+
+// A game written with this in front is taken as it is, even when it looks like a typo.
+pub(crate) const AS_IS: char = '=';
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+// A game in a guild's catalog, changed through `settings game`.
+pub(crate) struct GameEntry {
+    pub name: String,
+    // Other names people use for the game, e.g. "lol" and "league" for League of Legends.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    // Used when `create` isn't given a player count.
+    #[serde(default)]
+    pub default_size: Option<u32>,
+    // Parties for the game can't be bigger than this.
+    #[serde(default)]
+    pub max_size: Option<u32>,
+    #[serde(default)]
+    pub emoji: Option<String>,
+    // Shown as the thumbnail of the game's party announcements.
+    #[serde(default)]
+    pub icon_url: Option<String>,
+    // Pinged when a party for the game gets created.
+    #[serde(default)]
+    pub ping_role: Option<i64>,
+    // How far apart the ratings in an lfg group can be. Ratings are ignored without one, and with one
+    // members without a rating are only grouped with each other.
+    #[serde(default)]
+    pub rank_spread: Option<u32>,
+    // The slots every party for the game gets, e.g. for raids.
+    #[serde(default)]
+    pub slots: Vec<Slot>,
+}
+
+impl GameEntry {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            aliases: Vec::new(),
+            default_size: None,
+            max_size: None,
+            emoji: None,
+            icon_url: None,
+            ping_role: None,
+            rank_spread: None,
+            slots: Vec::new(),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+
+    // The name along with its emoji, if it has one.
+    pub(crate) fn display_name(&self) -> String {
+        match &self.emoji {
+            Some(emoji) => format!("{} {}", emoji, self.name),
+            None => self.name.clone()
+        }
+    }
+}
+
+pub(crate) enum Resolved<'a> {
+    Found(&'a GameEntry),
+    // Close enough to games in the catalog that it is probably a typo.
+    Misspelt(Vec<String>),
+    // Not in the catalog, and nothing like anything that is, or asked for as it is. Parties can
+    // still be made for it, under this name.
+    Unknown(String)
+}
+
+// Looks a game up by its name or any of its aliases, ignoring case.
+pub(crate) fn resolve<'a>(games: &'a [GameEntry], name: &str) -> Resolved<'a> {
+    let (name, as_is) = match name.strip_prefix(AS_IS) {
+        Some(stripped) if !stripped.is_empty() => (stripped, true),
+        _ => (name, false)
+    };
+
+    if let Some(game) = find(games, name) {
+        return Resolved::Found(game)
+    }
+    if as_is {
+        return Resolved::Unknown(name.to_string())
+    }
+
+    let lowercase = name.to_lowercase();
+    let mut suggestions: Vec<(usize, &str)> = games
+        .iter()
+        .filter_map(|game| {
+            std::iter::once(&game.name)
+                .chain(game.aliases.iter())
+                .map(|candidate| distance(&lowercase, &candidate.to_lowercase()))
+                .min()
+                .filter(|distance| is_close(&lowercase, *distance))
+                .map(|distance| (distance, game.name.as_str()))
+        })
+        .collect();
+
+    if suggestions.is_empty() {
+        return Resolved::Unknown(name.to_string())
+    }
+
+    suggestions.sort();
+    Resolved::Misspelt(suggestions
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name.to_string())
+        .collect())
+}
+
+pub(crate) fn find<'a>(games: &'a [GameEntry], name: &str) -> Option<&'a GameEntry> {
+    games.iter().find(|game| game.matches(name))
+}
+
+// Short names only get a typo or two, so "lol" doesn't get mistaken for "wow".
+fn is_close(name: &str, distance: usize) -> bool {
+    distance <= (name.chars().count() / 3).max(1)
+}
+
+// The Levenshtein distance from `name` to `candidate`, or 0 when `name` is long enough and the
+// candidate starts with it, so "league" still finds "League of Legends".
+fn distance(name: &str, candidate: &str) -> usize {
+    if name.chars().count() >= MIN_PREFIX && candidate.starts_with(name) {
+        return 0
+    }
+
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut previous: Vec<usize> = (0..=candidate.len()).collect();
+    for (i, name_char) in name.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, candidate_char) in candidate.iter().enumerate() {
+            let substitution = previous[j] + if name_char == *candidate_char { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[candidate.len()]
+}
+
+#[command]
+#[description = "Lists the games this server knows about."]
+async fn games(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let settings = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?.settings;
+    let language = i18n::language_for(ctx, msg).await;
+
+    let list: Vec<String> = settings.games
+        .iter()
+        .map(|game| {
+            let mut line = format!("**{}**", game.display_name());
+            if !game.aliases.is_empty() {
+                line.push_str(&format!(" ({})", game.aliases.join(", ")));
+            }
+            if let Some(size) = game.default_size {
+                line.push_str(&format!(" · {}", tr(&language, "games.default_size", &[("size", &size)])));
+            }
+            if let Some(size) = game.max_size {
+                line.push_str(&format!(" · {}", tr(&language, "games.max_size", &[("size", &size)])));
+            }
+            if !game.slots.is_empty() {
+                line.push_str(&format!(" · {}", slots::describe(&game.slots)));
+            }
+            if let Some(spread) = game.rank_spread {
+                line.push_str(&format!(" · {}", tr(&language, "games.rank_spread", &[("spread", &spread)])));
+            }
+            line
+        })
+        .collect();
+
+    msg.channel_id.send_message(&ctx.http, |cm| {
+        cm.embed(|ce| {
+            ce.title(tr(&language, "games.title", &[]));
+            if list.is_empty() {
+                ce.description(tr(&language, "games.empty", &[]));
+            } else {
+                ce.description(list.join("\n"));
+            }
+            ce.colour(Colour::DARK_GOLD);
+            ce
+        });
+        cm
+    }).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Adds games to the server's catalog, or changes and removes them."]
+#[usage = "add <game> | remove <game> | alias <game> <alias>... | size <game> <default> [max] | emoji <game> <emoji|off> | icon <game> <url|off> | role <game> <@role|off> | spread <game> <spread|off> | slots <game> <name=count,...|off>"]
+#[example = "add \"League of Legends\""]
+#[example = "alias \"League of Legends\" lol league"]
+#[example = "size \"League of Legends\" 5 5"]
+#[min_args(2)]
+async fn game(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let language = i18n::language_for(ctx, msg).await;
+    let limits = ctx.data.read().await.get::<Config>().unwrap().party.clone();
+
+    let action = args.single::<String>()?.to_lowercase();
+    let name = args.single_quoted::<String>()?;
+    let games = &mut server_data.settings.games;
+
+    let reply = match action.as_str() {
+        "add" => {
+            if find(games, &name).is_some() {
+                tr(&language, "games.exists", &[("game", &name)])
+            } else {
+                games.push(GameEntry::new(name.clone()));
+                tr(&language, "games.added", &[("game", &name)])
+            }
+        },
+        "remove" => {
+            let before = games.len();
+            games.retain(|game| !game.matches(&name));
+            if games.len() == before {
+                tr(&language, "games.not_found", &[("game", &name)])
+            } else {
+                tr(&language, "games.removed", &[("game", &name)])
+            }
+        },
+        _ => {
+            let game = match games.iter_mut().find(|game| game.matches(&name)) {
+                Some(game) => game,
+                None => {
+                    let reply = tr(&language, "games.not_found", &[("game", &name)]);
+                    msg.channel_id.say(&ctx.http, reply).await?;
+                    return Ok(())
+                }
+            };
+
+            let valid = match action.as_str() {
+                "alias" => {
+                    let mut added = false;
+                    while let Ok(alias) = args.single_quoted::<String>() {
+                        if !game.matches(&alias) {
+                            game.aliases.push(alias);
+                        }
+                        added = true;
+                    }
+                    added
+                },
+                "size" => {
+                    let default_size = args.single::<u32>().ok();
+                    let max_size = args.single::<u32>().ok().or(default_size);
+                    match (default_size, max_size) {
+                        (Some(default_size), Some(max_size))
+                            if default_size <= max_size
+                                && default_size >= limits.min_players
+                                && max_size <= limits.max_players => {
+                            game.default_size = Some(default_size);
+                            game.max_size = Some(max_size);
+                            true
+                        },
+                        _ => false
+                    }
+                },
+                "emoji" => match args.single::<String>()?.as_str() {
+                    "off" => {
+                        game.emoji = None;
+                        true
+                    },
+                    emoji => {
+                        game.emoji = Some(emoji.to_string());
+                        true
+                    }
+                },
+                "icon" => match args.single::<String>()?.as_str() {
+                    "off" => {
+                        game.icon_url = None;
+                        true
+                    },
+                    url if url.starts_with("https://") || url.starts_with("http://") => {
+                        game.icon_url = Some(url.to_string());
+                        true
+                    },
+                    _ => false
+                },
+                "spread" => match args.single::<String>()?.as_str() {
+                    "off" => {
+                        game.rank_spread = None;
+                        true
+                    },
+                    spread => match spread.parse::<u32>() {
+                        Ok(spread) => {
+                            game.rank_spread = Some(spread);
+                            true
+                        },
+                        Err(_) => false
+                    }
+                },
+                "slots" => match args.single::<String>()?.as_str() {
+                    "off" => {
+                        game.slots.clear();
+                        true
+                    },
+                    spec => match slots::parse(spec, limits.max_players) {
+                        Ok(slots) => {
+                            game.slots = slots;
+                            true
+                        },
+                        Err(_) => false
+                    }
+                },
+                "role" => match args.current() {
+                    Some("off") => {
+                        game.ping_role = None;
+                        true
+                    },
+                    _ => match msg.mention_roles.first() {
+                        Some(role) => {
+                            game.ping_role = Some(role.0 as i64);
+                            true
+                        },
+                        None => false
+                    }
+                },
+                _ => {
+                    let reply = tr(&language, "games.unknown_action", &[("action", &action)]);
+                    msg.channel_id.say(&ctx.http, reply).await?;
+                    return Ok(())
+                }
+            };
+
+            if valid {
+                tr(&language, "games.updated", &[("game", &game.name)])
+            } else {
+                let reply = tr(&language, "games.invalid_value", &[("action", &action)]);
+                msg.channel_id.say(&ctx.http, reply).await?;
+                return Ok(())
+            }
+        }
+    };
+
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
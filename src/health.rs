@@ -0,0 +1,86 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+use serenity::{
+    client::bridge::gateway::ShardManager,
+    gateway::ConnectionStage,
+    prelude::*
+};
+use crate::config::Config;
+use crate::db::{self, Database};
+use crate::metrics;
+use crate::party_timer::PartyTimers;
+
+// How long the database gets to answer a ping before it counts as down.
+const DB_PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) struct ShardManagerContainer;
+
+impl TypeMapKey for ShardManagerContainer {
+    type Value = Arc<Mutex<ShardManager>>;
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct HealthReport {
+    pub healthy: bool,
+    pub shards: Vec<ShardHealth>,
+    pub database: bool,
+    pub parties: usize,
+    pub collectors: i64
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ShardHealth {
+    pub id: u64,
+    pub stage: String,
+    pub connected: bool,
+    pub latency_ms: Option<u128>
+}
+
+// The bot is healthy when every shard is connected and the database answers.
+pub(crate) async fn check(data: &Arc<RwLock<TypeMap>>) -> HealthReport {
+    let (shard_manager, client, config, timers) = {
+        let data = data.read().await;
+        (
+            data.get::<ShardManagerContainer>().cloned(),
+            data.get::<Database>().cloned(),
+            data.get::<Config>().cloned(),
+            data.get::<PartyTimers>().cloned()
+        )
+    };
+
+    let mut shards = Vec::new();
+    if let Some(shard_manager) = shard_manager {
+        let shard_manager = shard_manager.lock().await;
+        let runners = shard_manager.runners.lock().await;
+        for (id, runner) in runners.iter() {
+            shards.push(ShardHealth {
+                id: id.0,
+                stage: runner.stage.to_string(),
+                connected: runner.stage == ConnectionStage::Connected,
+                latency_ms: runner.latency.map(|latency| latency.as_millis())
+            });
+        }
+    }
+
+    let database = match (client, config) {
+        (Some(client), Some(config)) => {
+            let ping = db::ping(&client, &config.mongo_db);
+            matches!(tokio::time::timeout(DB_PING_TIMEOUT, ping).await, Ok(Ok(_)))
+        },
+        _ => false
+    };
+
+    let parties = match timers {
+        Some(timers) => timers.lock().await.len(),
+        None => 0
+    };
+
+    HealthReport {
+        healthy: !shards.is_empty() && shards.iter().all(|shard| shard.connected) && database,
+        shards,
+        database,
+        parties,
+        collectors: metrics::REACTION_COLLECTORS.get()
+    }
+}
@@ -0,0 +1,60 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use hyper::{
+    Body, Request, Response, Server, StatusCode,
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn}
+};
+use serenity::prelude::*;
+use tracing::{error, info};
+use crate::{health, metrics};
+
+// The small HTTP server the bot runs next to the gateway, for `/metrics` and `/health`.
+pub(crate) async fn serve(addr: SocketAddr, data: Arc<RwLock<TypeMap>>) {
+    let make_service = make_service_fn(move |_| {
+        let data = data.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| handle(request, data.clone())))
+        }
+    });
+
+    info!(%addr, "Serving HTTP");
+    if let Err(why) = Server::bind(&addr).serve(make_service).await {
+        error!(error = ?why, "HTTP server stopped");
+    }
+}
+
+async fn handle(
+    request: Request<Body>,
+    data: Arc<RwLock<TypeMap>>
+) -> Result<Response<Body>, Infallible> {
+    let response = match request.uri().path() {
+        "/metrics" => match metrics::render() {
+            Ok((content_type, buffer)) => respond(StatusCode::OK, &content_type, buffer),
+            Err(why) => respond(StatusCode::INTERNAL_SERVER_ERROR, "text/plain", why.to_string())
+        },
+        "/health" => {
+            let report = health::check(&data).await;
+            let status = if report.healthy {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            let body = serde_json::to_vec(&report).unwrap_or_default();
+            respond(status, "application/json", body)
+        },
+        _ => respond(StatusCode::NOT_FOUND, "text/plain", "Not Found")
+    };
+
+    Ok(response)
+}
+
+fn respond<B: Into<Body>>(status: StatusCode, content_type: &str, body: B) -> Response<Body> {
+    let mut response = Response::new(body.into());
+    *response.status_mut() = status;
+    if let Ok(content_type) = content_type.parse() {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+
+    response
+}
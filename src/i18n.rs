@@ -0,0 +1,195 @@
+use std::{collections::HashMap, fmt::Display};
+
+use lazy_static::lazy_static;
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*
+};
+use tracing::error;
+use crate::db::{DatabaseServer, ServerSettings};
+
+pub(crate) const DEFAULT_LANGUAGE: &str = "en";
+
+// Every language there is a catalog for, with its name in that language.
+pub(crate) const LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"),
+    ("de", "Deutsch")
+];
+
+lazy_static! {
+    static ref CATALOGS: HashMap<&'static str, HashMap<String, String>> = {
+        let mut catalogs = HashMap::new();
+        catalogs.insert("en", load(include_str!("../locales/en.toml")));
+        catalogs.insert("de", load(include_str!("../locales/de.toml")));
+        catalogs
+    };
+}
+
+// Flattens the sections of a catalog, so `ratelimited` under `[dispatch]` becomes
+// `dispatch.ratelimited`.
+fn load(source: &str) -> HashMap<String, String> {
+    let table: toml::value::Table = toml::from_str(source).expect("Message catalog is not valid TOML");
+    let mut messages = HashMap::new();
+    flatten(&mut messages, "", &table);
+    messages
+}
+
+fn flatten(messages: &mut HashMap<String, String>, prefix: &str, table: &toml::value::Table) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::String(message) => {
+                messages.insert(key, message.clone());
+            },
+            toml::Value::Table(section) => flatten(messages, &key, section),
+            _ => {}
+        }
+    }
+}
+
+// The message for `key` in `language` with its placeholders filled in. Messages that haven't been
+// translated yet are shown in English, and unknown keys come back as they are, so plain text can
+// be passed through as well.
+pub(crate) fn tr(language: &str, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let message = CATALOGS
+        .get(language)
+        .and_then(|catalog| catalog.get(key))
+        .or_else(|| CATALOGS.get(DEFAULT_LANGUAGE).and_then(|catalog| catalog.get(key)));
+    let mut message = match message {
+        Some(message) => message.clone(),
+        None => key.to_string()
+    };
+
+    for (name, value) in args {
+        message = message.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+
+    message
+}
+
+// Like `tr`, for text that is written in English where it is declared, like the descriptions of
+// commands. Catalogs only translate it, so without a translation it stays `fallback`.
+pub(crate) fn tr_or(language: &str, key: &str, fallback: Option<&str>) -> Option<String> {
+    let message = tr(language, key, &[]);
+    if message == key { fallback.map(str::to_string) } else { Some(message) }
+}
+
+fn language_name(language: &str) -> Option<&'static str> {
+    LANGUAGES.iter().find(|(code, _)| *code == language).map(|(_, name)| *name)
+}
+
+// The language everyone in the guild sees, unless they picked their own.
+pub(crate) fn guild_language(settings: &ServerSettings) -> &str {
+    settings.language.as_deref().unwrap_or(DEFAULT_LANGUAGE)
+}
+
+// An answer in the default language beats no answer when the database can't be reached.
+async fn settings_in(ctx: &Context, guild: GuildId) -> Option<ServerSettings> {
+    match DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await {
+        Ok(server_data) => Some(server_data.settings),
+        Err(why) => {
+            error!(guild = %guild, error = ?why, "Could not read the language settings");
+            None
+        }
+    }
+}
+
+pub(crate) async fn language_in(ctx: &Context, guild: GuildId) -> String {
+    match settings_in(ctx, guild).await {
+        Some(settings) => guild_language(&settings).to_string(),
+        None => DEFAULT_LANGUAGE.to_string()
+    }
+}
+
+// The language to answer the author of a message in.
+pub(crate) async fn language_for(ctx: &Context, msg: &Message) -> String {
+    let guild = match msg.guild_id {
+        Some(guild) => guild,
+        None => return DEFAULT_LANGUAGE.to_string()
+    };
+
+    let settings = match settings_in(ctx, guild).await {
+        Some(settings) => settings,
+        None => return DEFAULT_LANGUAGE.to_string()
+    };
+    match settings.member_languages.get(&msg.author.id.0.to_string()) {
+        Some(language) => language.clone(),
+        None => guild_language(&settings).to_string()
+    }
+}
+
+fn unknown_language(language: &str, requested: &str) -> String {
+    let languages: Vec<String> = LANGUAGES
+        .iter()
+        .map(|(code, name)| format!("`{}` ({})", code, name))
+        .collect();
+
+    tr(language, "settings.language_unknown", &[
+        ("language", &requested),
+        ("languages", &languages.join(", "))
+    ])
+}
+
+#[command]
+#[aliases(lang)]
+#[description = "Changes the language the bot speaks in this server."]
+#[usage = "<language>"]
+#[example = "de"]
+#[num_args(1)]
+async fn language(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let requested = args.single::<String>()?.to_lowercase();
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+
+    let name = match language_name(&requested) {
+        Some(name) => name,
+        None => {
+            let reply = unknown_language(guild_language(&server_data.settings), &requested);
+            msg.channel_id.say(&ctx.http, reply).await?;
+            return Ok(())
+        }
+    };
+
+    server_data.settings.language = Some(requested.clone());
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    msg.channel_id.say(&ctx.http, tr(&requested, "settings.language_set", &[("language", &name)])).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases(mylang)]
+#[description = "Changes the language the bot speaks to you in this server, or goes back to the server's."]
+#[usage = "<language> | reset"]
+#[example = "en"]
+#[only_in(guilds)]
+#[num_args(1)]
+async fn mylanguage(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let requested = args.single::<String>()?.to_lowercase();
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let member = msg.author.id.0.to_string();
+
+    let reply = if requested == "reset" {
+        server_data.settings.member_languages.remove(&member);
+        tr(guild_language(&server_data.settings), "settings.my_language_reset", &[])
+    } else {
+        match language_name(&requested) {
+            Some(name) => {
+                server_data.settings.member_languages.insert(member, requested.clone());
+                tr(&requested, "settings.my_language_set", &[("language", &name)])
+            },
+            None => {
+                let reply = unknown_language(&language_for(ctx, msg).await, &requested);
+                msg.channel_id.say(&ctx.http, reply).await?;
+                return Ok(())
+            }
+        }
+    };
+
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
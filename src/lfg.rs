@@ -0,0 +1,472 @@
+use std::{collections::HashMap, convert::TryFrom, sync::Arc, time::{Duration, Instant}};
+
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*
+};
+use tracing::{error, info, warn};
+use crate::{CAN_CREATE_CHECK, THUMBS_UP, create_party_channels, spawn_party_collector};
+use crate::audit_log::{self, PartyEvent};
+use crate::config::Config;
+use crate::db::{DatabaseServer, ServerSettings};
+use crate::embeds::{avatar_url, party_embed};
+use crate::error::PartyError;
+use crate::games::{self, Resolved};
+use crate::i18n::{self, tr};
+use crate::metrics;
+use crate::party_groups::Group;
+use crate::party_timer;
+use crate::ranks;
+use crate::slots;
+
+// Someone waiting for a group.
+#[derive(Debug, Clone)]
+pub(crate) struct QueueEntry {
+    pub user: UserId,
+    pub name: String,
+    pub game: String,
+    pub size: u32,
+    // Their rating in the game when they queued, if they set one.
+    pub rating: Option<u32>,
+    // Where they queued, so that's where they hear back.
+    pub channel: ChannelId,
+    pub queued_at: Instant
+}
+
+impl QueueEntry {
+    fn wants_same(&self, other: &QueueEntry) -> bool {
+        self.size == other.size && self.game.eq_ignore_ascii_case(&other.game)
+    }
+}
+
+// Everyone waiting for a group in each guild, oldest first. It only lives in memory, so the queue
+// starts empty after a restart.
+pub(crate) struct LfgQueue;
+
+impl TypeMapKey for LfgQueue {
+    type Value = Arc<Mutex<HashMap<GuildId, Vec<QueueEntry>>>>;
+}
+
+// Whether a rating falls within `spread` above `lowest`.
+fn fits(rating: u32, lowest: u32, spread: u32) -> bool {
+    rating >= lowest && rating - lowest <= spread
+}
+
+// The entries that make a group with the one that just queued, if enough people are waiting for the
+// same game and size. With a `spread`, everyone in the group is within it of each other, and members
+// without a rating are only grouped with each other. The ones who waited the longest go first.
+fn find_match(queue: &[QueueEntry], newest: &QueueEntry, spread: Option<u32>) -> Option<Vec<usize>> {
+    let candidates: Vec<usize> = queue
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.wants_same(newest))
+        .map(|(i, _)| i)
+        .collect();
+
+    let spread = match spread {
+        Some(spread) => spread,
+        None => return take_group(candidates, newest.size)
+    };
+
+    let rating = match newest.rating {
+        Some(rating) => rating,
+        None => {
+            let unrated = candidates.into_iter().filter(|&i| queue[i].rating.is_none()).collect();
+            return take_group(unrated, newest.size)
+        }
+    };
+
+    // Every group has a lowest rating, so trying each rating the newest entry fits above finds any
+    // group there is.
+    let mut lowest_ratings: Vec<u32> = candidates
+        .iter()
+        .filter_map(|&i| queue[i].rating)
+        .filter(|&lowest| fits(rating, lowest, spread))
+        .collect();
+    lowest_ratings.sort_unstable();
+    lowest_ratings.dedup();
+
+    lowest_ratings.into_iter().find_map(|lowest| {
+        let fitting = candidates
+            .iter()
+            .copied()
+            .filter(|&i| queue[i].rating.map_or(false, |rating| fits(rating, lowest, spread)))
+            .collect();
+        take_group(fitting, newest.size)
+    })
+}
+
+fn take_group(mut candidates: Vec<usize>, size: u32) -> Option<Vec<usize>> {
+    candidates.truncate(size as usize);
+    if candidates.len() == size as usize { Some(candidates) } else { None }
+}
+
+#[command]
+#[checks(CanCreate)]
+#[description = "Queues you up for a game. Once enough people are waiting, a party gets made for all of you."]
+#[usage = "<game> [size] | leave"]
+#[example = "Valorant 5"]
+#[example = "\"League of Legends\""]
+#[example = "leave"]
+#[min_args(1)]
+#[max_args(2)]
+async fn lfg(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let language = i18n::language_for(ctx, msg).await;
+    let queue = ctx.data.read().await.get::<LfgQueue>().unwrap().clone();
+
+    if args.current() == Some("leave") {
+        let mut queue = queue.lock().await;
+        let entries = queue.entry(guild).or_default();
+        let before = entries.len();
+        entries.retain(|entry| entry.user != msg.author.id);
+        let reply = if entries.len() == before { "lfg.not_queued" } else { "lfg.left" };
+        msg.channel_id.say(&ctx.http, tr(&language, reply, &[])).await?;
+        return Ok(())
+    }
+
+    let name = args.single_quoted::<String>()?;
+    let size = match args.single::<String>() {
+        Ok(size) => Some(size.parse::<u32>().map_err(|_| PartyError::InvalidPlayers(size))?),
+        Err(_) => None
+    };
+
+    let limits = ctx.data.read().await.get::<Config>().unwrap().party.clone();
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let owners = server_data.owners();
+    let settings = server_data.settings;
+    let (game, default_size, max_size, spread) = match games::resolve(&settings.games, &name) {
+        // A game with slots makes groups as big as its slots add up to, like `create` does.
+        Resolved::Found(game) if !game.slots.is_empty() => {
+            let max_size = game.max_size.unwrap_or(limits.max_players).min(limits.max_players);
+            let total = slots::total(&game.slots).ok_or(PartyError::TooManyPeople(max_size))?;
+            if size.map_or(false, |size| size != total) {
+                return Err(PartyError::InvalidSlots(slots::describe(&game.slots)).into())
+            }
+            (game.name.clone(), Some(total), max_size, game.rank_spread)
+        },
+        Resolved::Found(game) => (
+            game.name.clone(),
+            game.default_size,
+            game.max_size.unwrap_or(limits.max_players).min(limits.max_players),
+            game.rank_spread
+        ),
+        Resolved::Misspelt(suggestions) => {
+            return Err(PartyError::UnknownGame { game: name, suggestions }.into())
+        },
+        Resolved::Unknown(name) => (name, None, limits.max_players, None)
+    };
+
+    let size = size.or(default_size).ok_or(PartyError::NoPlayers)?;
+    if size > max_size {
+        return Err(PartyError::TooManyPeople(max_size).into())
+    }
+    if size < limits.min_players {
+        return Err(PartyError::TooLittlePeople(limits.min_players).into())
+    }
+
+    // Whoever waited the longest owns the party, and nobody can own two.
+    if owners.contains(&(msg.author.id.0 as i64)) {
+        return Err(PartyError::PartyOwner.into())
+    }
+
+    let entry = QueueEntry {
+        user: msg.author.id,
+        name: msg.author.name.clone(),
+        rating: ranks::rating(&settings, msg.author.id, &game),
+        game,
+        size,
+        channel: msg.channel_id,
+        queued_at: Instant::now()
+    };
+
+    // Queueing again replaces what they queued for before. Anyone who made a party of their own
+    // while waiting can't be in this one either, so they are done waiting.
+    let (matched, waiting) = {
+        let mut queue = queue.lock().await;
+        let entries = queue.entry(guild).or_default();
+        entries.retain(|queued| queued.user != entry.user && !owners.contains(&(queued.user.0 as i64)));
+        entries.push(entry.clone());
+
+        match find_match(entries, &entry, spread) {
+            Some(matching) => {
+                let mut matched = Vec::new();
+                for i in matching.into_iter().rev() {
+                    matched.insert(0, entries.remove(i));
+                }
+                (Some(matched), 0)
+            },
+            None => {
+                (None, entries.iter().filter(|queued| queued.wants_same(&entry)).count())
+            }
+        }
+    };
+
+    match matched {
+        Some(matched) => {
+            // The same group would only fail the same way again, so they queue up again themselves.
+            if let Err(why) = create_group(ctx, guild, msg, &matched).await {
+                let mentions: Vec<String> = matched.iter().map(|entry| entry.user.mention()).collect();
+                let reply = tr(&language, "lfg.failed", &[("members", &mentions.join(" "))]);
+                if let Err(why) = msg.channel_id.say(&ctx.http, reply).await {
+                    metrics::record_error(&why);
+                    error!(error = ?why, "Could not tell a group it could not be made");
+                }
+                return Err(why.into())
+            }
+        },
+        None => {
+            let timeout_mins = limits.lfg_timeout_mins;
+            let reply = tr(&language, "lfg.queued", &[
+                ("game", &entry.game),
+                ("waiting", &waiting),
+                ("size", &entry.size),
+                ("minutes", &timeout_mins)
+            ]);
+            msg.channel_id.say(&ctx.http, reply).await?;
+            time_out(ctx, guild, entry, Duration::from_secs(timeout_mins * 60));
+        }
+    }
+
+    Ok(())
+}
+
+// Takes the entry out of the queue once it has waited too long, unless it was matched or replaced.
+fn time_out(ctx: &Context, guild: GuildId, entry: QueueEntry, timeout: Duration) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        tokio::time::delay_for(timeout).await;
+
+        let queue = ctx.data.read().await.get::<LfgQueue>().unwrap().clone();
+        let removed = {
+            let mut queue = queue.lock().await;
+            let entries = queue.entry(guild).or_default();
+            let before = entries.len();
+            entries.retain(|queued| !(queued.user == entry.user && queued.queued_at == entry.queued_at));
+            entries.len() != before
+        };
+
+        if removed {
+            let language = i18n::language_in(&ctx, guild).await;
+            let reply = tr(&language, "lfg.timed_out", &[
+                ("member", &entry.user.mention()),
+                ("game", &entry.game)
+            ]);
+            if let Err(why) = entry.channel.say(&ctx.http, reply).await {
+                metrics::record_error(&why);
+                error!(error = ?why, "Could not tell a member their queue entry timed out");
+            }
+        }
+    });
+}
+
+// Makes a party for everyone that got matched, with whoever waited the longest as its owner.
+// Members that can't be found anymore, e.g. because they left the server, are left out and their
+// spots stay open.
+async fn create_group(
+    ctx: &Context,
+    guild: GuildId,
+    msg: &Message,
+    matched: &[QueueEntry]
+) -> Result<(), PartyError> {
+    let mut members = Vec::new();
+    for entry in matched {
+        match guild.member(&ctx.http, entry.user).await {
+            Ok(member) => members.push((entry, member)),
+            Err(why) => {
+                metrics::record_error(&why);
+                warn!(user = %entry.user, error = ?why, "Leaving an unreachable member out of a group");
+            }
+        }
+    }
+
+    let owner = match members.first() {
+        Some((_, owner)) => owner.user.clone(),
+        None => return Ok(())
+    };
+    let game = matched[0].game.clone();
+    let size = matched[0].size;
+    let settings = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?.settings;
+    let language = i18n::guild_language(&settings);
+    let title = tr(language, "lfg.title", &[("game", &game)]);
+
+    let (party_role_id, party_text_id, party_voice_id) =
+        create_party_channels(ctx, guild, language, &owner.name, &title, size).await?;
+
+    let mut group_data = Group::new(
+        owner.id.0 as i64,
+        size as i64,
+        title,
+        game,
+        party_voice_id.0 as i64,
+        party_text_id.0 as i64,
+        party_role_id.0 as i64
+    ).await;
+    // Everyone picks their slot once they're in.
+    group_data.slots = games::find(&settings.games, group_data.game())
+        .map(|game| game.slots.clone())
+        .unwrap_or_default();
+
+    let result = fill_group(ctx, guild, msg, &settings, &owner, members, group_data).await;
+
+    // A party that never got made doesn't leave its channels behind.
+    if result.is_err() {
+        for channel in [party_text_id, party_voice_id].iter() {
+            if let Err(why) = ctx.http.delete_channel(channel.0).await {
+                metrics::record_error(&why);
+                error!(channel = %channel, error = ?why, "Could not clean up the channel of a failed group");
+            }
+        }
+        if let Err(why) = ctx.http.delete_role(guild.0, party_role_id.0).await {
+            metrics::record_error(&why);
+            error!(role = %party_role_id, error = ?why, "Could not clean up the role of a failed group");
+        }
+    }
+
+    result
+}
+
+// Everything `create_group` does once the channels are there.
+async fn fill_group(
+    ctx: &Context,
+    guild: GuildId,
+    msg: &Message,
+    settings: &ServerSettings,
+    owner: &User,
+    members: Vec<(&QueueEntry, Member)>,
+    mut group_data: Group
+) -> Result<(), PartyError> {
+    let channel = msg.channel_id;
+    let language = i18n::guild_language(settings);
+    let party_role_id = RoleId(group_data.role_id as u64);
+    let game = group_data.game().to_string();
+
+    let mut mentions = Vec::new();
+    for (entry, mut member) in members {
+        if let Err(why) = member.add_role(&ctx.http, party_role_id).await {
+            metrics::record_error(&why);
+            warn!(user = %entry.user, error = ?why, "Leaving a member without the party role out of a group");
+            continue
+        }
+        group_data.add_player(entry.user.0 as i64).await;
+        group_data.add_player_name(entry.name.clone()).await;
+        mentions.push(entry.user.mention());
+    }
+
+    let avatar_url = avatar_url(ctx, owner).await?;
+    let mut matched = tr(language, "lfg.matched", &[
+        ("members", &mentions.join(" ")),
+        ("game", &game),
+        ("channel", &ChannelId(group_data.text_id as u64).mention())
+    ]);
+    if !group_data.slots.is_empty() {
+        matched.push(' ');
+        matched.push_str(&tr(language, "lfg.pick_slot", &[]));
+    }
+
+    let embed_message = channel.send_message(&ctx.http, |cm| {
+        cm.content(matched);
+        cm.embed(|ce| party_embed(ce, &group_data, owner, &avatar_url, None, settings));
+        cm
+    }).await?;
+
+    if group_data.slots.is_empty() {
+        embed_message.react(
+            &ctx.http,
+            ReactionType::try_from(THUMBS_UP).unwrap()
+        ).await?;
+    }
+    for slot in group_data.slots.iter() {
+        embed_message.react(&ctx.http, ReactionType::try_from(slot.emoji.as_str()).unwrap()).await?;
+    }
+
+    // The `lfg` that completed the group gets cleaned up with it, like `create` would.
+    group_data.set_messages(channel.0 as i64, msg.id.0 as i64, embed_message.id.0 as i64);
+    let party_id = group_data.role_id;
+
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    server_data.add_party(group_data.clone()).await;
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    audit_log::log(ctx, guild, &group_data, None, PartyEvent::Created).await;
+    info!(
+        guild = %guild,
+        game = %game,
+        size = group_data.max_player_amount(),
+        members = group_data.player_amount(),
+        "Matched a group from the queue"
+    );
+
+    party_timer::track(ctx, guild, party_id).await;
+    spawn_party_collector(ctx, guild, party_id).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Everyone wants a group of three for the same game, unless a test says otherwise.
+    fn entry(user: u64, rating: Option<u32>) -> QueueEntry {
+        QueueEntry {
+            user: UserId(user),
+            name: user.to_string(),
+            game: "Valorant".to_string(),
+            size: 3,
+            rating,
+            channel: ChannelId(1),
+            queued_at: Instant::now()
+        }
+    }
+
+    #[test]
+    fn unrated_members_make_a_group_once_enough_are_waiting() {
+        let queue = vec![entry(1, None), entry(2, None), entry(3, None)];
+        assert_eq!(find_match(&queue, &queue[2], None), Some(vec![0, 1, 2]));
+        assert_eq!(find_match(&queue[..2], &queue[1], None), None);
+    }
+
+    #[test]
+    fn only_the_same_game_and_size_make_a_group() {
+        let mut other_game = entry(2, None);
+        other_game.game = "Overwatch".to_string();
+        let mut other_size = entry(3, None);
+        other_size.size = 5;
+        let queue = vec![entry(1, None), other_game, other_size, entry(4, None), entry(5, None)];
+
+        assert_eq!(find_match(&queue, &queue[4], None), Some(vec![0, 3, 4]));
+    }
+
+    #[test]
+    fn without_a_spread_ratings_dont_matter() {
+        let queue = vec![entry(1, Some(1000)), entry(2, None), entry(3, Some(3000))];
+        assert_eq!(find_match(&queue, &queue[2], None), Some(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn rated_members_are_grouped_within_the_spread() {
+        let queue = vec![
+            entry(1, Some(1000)),
+            entry(2, Some(1500)),
+            entry(3, Some(1050)),
+            entry(4, Some(1080))
+        ];
+        assert_eq!(find_match(&queue, &queue[3], Some(100)), Some(vec![0, 2, 3]));
+    }
+
+    #[test]
+    fn rated_members_too_far_apart_keep_waiting() {
+        let queue = vec![entry(1, Some(1000)), entry(2, Some(1300)), entry(3, Some(1150))];
+        assert_eq!(find_match(&queue, &queue[2], Some(100)), None);
+    }
+
+    #[test]
+    fn rated_and_unrated_members_are_not_grouped_together() {
+        let queue = vec![entry(1, None), entry(2, None), entry(3, Some(1000))];
+        assert_eq!(find_match(&queue, &queue[2], Some(100)), None);
+
+        let queue = vec![entry(1, None), entry(2, Some(1000)), entry(3, None), entry(4, None)];
+        assert_eq!(find_match(&queue, &queue[3], Some(100)), Some(vec![0, 2, 3]));
+    }
+}
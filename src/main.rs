@@ -1,16 +1,39 @@
+pub mod archive;
+pub mod audit_log;
+pub mod config;
+pub mod create_args;
 pub mod db;
+pub mod diagnostics;
+pub mod embeds;
+pub mod error;
+pub mod games;
+pub mod health;
+pub mod http;
+pub mod i18n;
+pub mod lfg;
+pub mod metrics;
 pub mod party_groups;
-
-use std::{collections::{HashSet, HashMap}, convert::TryFrom, env, fmt, time::Duration};
+pub mod party_timer;
+pub mod permissions;
+pub mod prefix;
+pub mod presence;
+pub mod ranks;
+pub mod sharding;
+pub mod slots;
+pub mod stats;
+pub mod subscriptions;
+pub mod voice;
+
+use std::{collections::{HashSet, HashMap}, convert::TryFrom, error::Error, sync::Arc, time::{Duration, Instant}};
 use serenity::{
     async_trait,
-    builder::CreateEmbedAuthor,
     client::Client,
-    framework::standard::{
-        Args, CommandResult, StandardFramework, DispatchError, HelpOptions,
-        help_commands::*, CommandGroup,
+    collector::ReactionCollectorBuilder,
+    framework::{Framework, standard::{
+        Args, CheckResult, CommandOptions, CommandResult, Delimiter, Reason, StandardFramework,
+        DispatchError, HelpOptions, help_commands::*, CommandGroup,
         macros::*,
-    },
+    }},
     futures::StreamExt,
     http::Http,
     model::prelude::*,
@@ -18,94 +41,148 @@ use serenity::{
     utils::Colour
 };
 use mongodb::{Client as ClientDB, options::ClientOptions};
+use tracing::{Span, debug, field, error, info, info_span, instrument, trace, warn};
+use tracing_futures::Instrument;
+use tracing_subscriber::EnvFilter;
 use db::Database;
+use crate::archive::ARCHIVE_COMMAND;
+use crate::audit_log::{LOGCHANNEL_COMMAND, PartyEvent};
+use crate::config::Config;
 use crate::db::DatabaseServer;
+use crate::diagnostics::{ABOUT_COMMAND, PING_COMMAND, StartTime};
+use crate::embeds::{EMBED_COMMAND, avatar_url, party_embed, update_party_embed};
+use crate::error::{ErrorReports, PartyError};
+use crate::games::{GAME_COMMAND, GAMES_COMMAND, Resolved};
+use crate::health::ShardManagerContainer;
+use crate::i18n::{LANGUAGE_COMMAND, MYLANGUAGE_COMMAND, tr};
+use crate::lfg::{LFG_COMMAND, LfgQueue};
 use crate::party_groups::Group;
+use crate::party_timer::PartyTimers;
+use crate::permissions::CREATEROLES_COMMAND;
+use crate::prefix::{GuildPrefixes, PREFIX_COMMAND, guild_prefix};
+use crate::presence::{STATUS_COMMAND, TemporaryStatus};
+use crate::ranks::RANK_COMMAND;
+use crate::stats::STATS_COMMAND;
+use crate::subscriptions::{LastNotified, LastPinged, QUIET_COMMAND, SUBSCRIBE_COMMAND, UNSUBSCRIBE_COMMAND};
+use crate::voice::VoiceActivity;
 
 /*
  * Thank you Kara-b
  * https://github.com/kara-b/kbot_rust/tree/01bbbec4c1ce6497e58141e0495441c5f446bd18
  */
 
-const THUMBS_UP: &str = "👍";
+pub(crate) const THUMBS_UP: &str = "👍";
 
-struct CommandCounter;
+// How long a reaction collector waits before checking that its party still exists.
+const COLLECTOR_CHECK: Duration = Duration::from_secs(60);
 
-impl TypeMapKey for CommandCounter {
-    type Value = HashMap<String, u64>;
-}
+// The parties whose announcement reactions are being collected, by the id of their role.
+struct PartyCollectors;
 
-enum PartyError {
-    NoGame,
-    NoTitle,
-    TooManyPeople,
-    TooLittlePeople,
-    PartyOwner
+impl TypeMapKey for PartyCollectors {
+    type Value = Arc<Mutex<HashSet<i64>>>;
 }
 
-impl fmt::Display for PartyError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
-            PartyError::NoGame => write!(f, "No game has been entered!"),
-            PartyError::NoTitle => write!(f, "No title has been entered!"),
-            PartyError::TooManyPeople => write!(f, "Can not have over 20 people per party!"),
-            PartyError::TooLittlePeople => write!(f, "Can't have a party with less than 2 people!"),
-            PartyError::PartyOwner => write!(f, "You already own a party. HMPH. NO MORE FOR YOU.")
-        }
-    }
+// Held while a member is taken out of their parties. A ban sends both a ban and a member removal,
+// and the second one has to see what the first one did, or the same party gets disbanded twice.
+struct MemberRemovals;
+
+impl TypeMapKey for MemberRemovals {
+    type Value = Arc<Mutex<()>>;
 }
 
 struct Handler;
 
+#[group]
+#[description = "Commands that tell you about the bot itself."]
+#[commands(ping, about, mylanguage)]
+struct General;
+
 #[group]
 #[description = "A group of commands that allow you to create guild parties!"]
 #[prefixes("party", "p")]
 #[only_in(guilds)]
-#[commands(create)]
+#[commands(create, disband, kick, edit, info, lfg, rank, games, subscribe, unsubscribe, quiet, stats)]
 struct Party;
 
+#[group]
+#[description = "Commands that change how the bot behaves in this server."]
+#[prefixes("settings", "set")]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[commands(prefix, language, createroles, embed, game, archive, logchannel)]
+struct Settings;
+
 #[group]
 #[description = "Commands only the owner can use to help the bot."]
 #[prefixes("owner", "own")]
-#[commands(stop)]
+#[commands(status)]
 #[owners_only]
 struct Owner;
 
 #[async_trait]
 impl EventHandler for Handler {
-    // Set a handler for the `message` event - so that whenever a new message
-    // is received - the closure (or function) passed will be called.
-    //
-    // Event handlers are dispatched through a threadpool, and so multiple
-    // events can be dispatched simultaneously.
-    async fn message(&self, ctx: Context, msg: Message) {
-        if msg.content == "!ping" {
-            // Sending a message can fail, due to a network error, an
-            // authentication error, or lack of permissions to post in the
-            // channel, so log to stdout when some error happens, with a
-            // description of it.
-            if let Err(why) = msg.channel_id.say(&ctx.http, "Pong!").await {
-                println!("Error sending message: {:?}", why);
-            }
+    async fn reaction_add(&self, ctx: Context, add_reaction: Reaction) {
+        if let Err(why) = party_timer::handle_keep_alive(&ctx, &add_reaction).await {
+            metrics::record_error(&*why);
+            error!(error = ?why, "Could not keep party alive");
         }
     }
 
-    async fn guild_delete(&self, _ctx: Context, _incomplete: GuildUnavailable, _: Option<Guild>) {
-        let id = _incomplete.id.0;
-        match DatabaseServer::delete(&_ctx, id as i64).await {
-            Ok(_) => {},
-            Err(why) => panic!("Error Deleting from Database\nReason: {}", why)
-        };
+    async fn guild_delete(&self, ctx: Context, incomplete: GuildUnavailable, _: Option<Guild>) {
+        // An outage sends this too, only forget the guild once the bot has really left it.
+        if incomplete.unavailable {
+            return;
+        }
+        if let Err(why) = DatabaseServer::delete(&ctx, incomplete.id.0 as i64).await {
+            error!(guild = %incomplete.id, "Error deleting guild from the database: {}", why);
+        }
+    }
+
+    async fn guild_member_removal(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        user: User,
+        _: Option<Member>
+    ) {
+        remove_member_from_parties(&ctx, guild_id, &user, false).await;
+    }
+
+    async fn guild_ban_addition(&self, ctx: Context, guild_id: GuildId, banned_user: User) {
+        remove_member_from_parties(&ctx, guild_id, &banned_user, true).await;
+    }
+
+    async fn voice_state_update(
+        &self,
+        ctx: Context,
+        _: Option<GuildId>,
+        old: Option<VoiceState>,
+        new: VoiceState
+    ) {
+        voice::record_activity(&ctx, old.as_ref(), &new).await;
     }
 
     async fn guild_create(&self, _ctx: Context, _guild: Guild, _: bool) {
         let id = _guild.id.0;
         let owner_id = _guild.owner_id.0;
-        DatabaseServer::get_or_insert_new(
+        let server_data = match DatabaseServer::get_or_insert_new(
             &_ctx,
             id as i64,
             Some(owner_id as i64)
-        ).await;
+        ).await {
+            Ok(server_data) => server_data,
+            Err(why) => {
+                error!(guild = %_guild.id, error = ?why, "Could not load the guild from the database");
+                return
+            }
+        };
+
+        // Pick back up the parties that were running before a restart or a reconnect.
+        for party in server_data.parties.iter() {
+            party_timer::track(&_ctx, _guild.id, party.role_id).await;
+            spawn_party_collector(&_ctx, _guild.id, party.role_id).await;
+        }
     }
 
     // Set a handler to be called on the `ready` event. This is called when a
@@ -113,24 +190,47 @@ impl EventHandler for Handler {
     // contains data like the current user's guild Ids, current user data,
     // private channels, and more.
     //
-    // In this case, just print what the current user's username is.
+    // In this case, just log what the current user's username is.
     async fn ready(&self, ctx: Context, ready: Ready) {
-        eprint!("\nNAME: {} is connected!\nID: {}\n", ready.user.name, ready.user.id);
-        ctx.set_activity(Activity::playing("Makin' Parties!")).await;
+        info!(name = %ready.user.name, id = %ready.user.id, "Connected");
+        let config = ctx.data.read().await.get::<Config>().unwrap().clone();
+        presence::start_rotation(&ctx, config.presence.clone());
+        party_timer::start_scheduler(&ctx, config.timers());
+    }
+}
+
+// `log_level` takes the same directives as `RUST_LOG`, and `log_format = "json"` switches to JSON
+// lines.
+fn init_logging(config: &Config) {
+    let filter = EnvFilter::new(&config.log_level);
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    if config.log_format == "json" {
+        subscriber.json().init()
+    } else {
+        subscriber.init()
     }
 }
 
 #[tokio::main]
 async fn main() {
-    // Configure the client with your Discord bot token in the environment.
-    let token = env::var("DISCORD_TOKEN")
-        .expect("Expected a token in the environment");
+    // Logging isn't set up until the config is loaded, so this is the one place that prints.
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(why) => {
+            eprintln!("Invalid configuration: {}", why);
+            std::process::exit(1);
+        }
+    };
 
+    init_logging(&config);
+
+    let token = config.discord_token.clone();
     let http = Http::new_with_token(&token);
 
     let (owners, bot_id) = match http.get_current_application_info().await {
         Ok(info) => {
-            let mut owners = HashSet::new();
+            let mut owners: HashSet<UserId> = config.owners.iter().map(|id| UserId(*id)).collect();
             if let Some(team) = info.team {
                 owners.insert(team.owner_user_id);
             } else {
@@ -150,7 +250,8 @@ async fn main() {
         .configure(|c| c
                     .with_whitespace(true)
                     .on_mention(Some(bot_id))
-                    .prefix(">?")
+                    .prefix(&config.prefix)
+                    .dynamic_prefix(guild_prefix)
                     .allow_dm(false)
                     .ignore_bots(true)
                     .owners(owners))
@@ -178,22 +279,24 @@ async fn main() {
     // reason or another. For example, when a user has exceeded a rate-limit or a command
     // can only be performed by the bot owner.
         .on_dispatch_error(dispatch_error)
-        .group(&PARTY_GROUP);
+        .help(&MY_HELP)
+        .group(&GENERAL_GROUP)
+        .group(&PARTY_GROUP)
+        .group(&SETTINGS_GROUP)
+        .group(&OWNER_GROUP);
 
     // Create a new instance of the Client, logging in as a bot. This will
     // automatically prepend your bot token with "Bot ", which is a requirement
     // by Discord for bot users.
     let mut bot_client = Client::builder(&token)
         .event_handler(Handler)
-        .framework(framework)
+        .framework(TracedFramework(framework))
         .await
         .expect("Err creating client");
 
     {
-        let mongo_url = env::var("MONGO_URL").expect("Expected MongoDB Connection String.");
-
         // Parse a connection string into an options struct.
-        let mut db_client_ops = ClientOptions::parse(mongo_url.as_str())
+        let mut db_client_ops = ClientOptions::parse(config.mongo_url.as_str())
             .await
             .expect("Could not parse");
         // Manually set an option.
@@ -203,77 +306,209 @@ async fn main() {
         let client = ClientDB::with_options(db_client_ops).expect("Could not connect to DB");
         let mut data = bot_client.data.write().await;
         data.insert::<Database>(client);
+        data.insert::<Config>(config.clone());
+        data.insert::<VoiceActivity>(HashMap::new());
+        data.insert::<PartyTimers>(Arc::new(Mutex::new(HashMap::new())));
+        data.insert::<PartyCollectors>(Arc::new(Mutex::new(HashSet::new())));
+        data.insert::<MemberRemovals>(Arc::new(Mutex::new(())));
+        data.insert::<TemporaryStatus>(None);
+        data.insert::<StartTime>(Instant::now());
+        data.insert::<GuildPrefixes>(HashMap::new());
+        data.insert::<ErrorReports>(HashMap::new());
+        data.insert::<LastNotified>(HashMap::new());
+        data.insert::<LastPinged>(HashMap::new());
+        data.insert::<LfgQueue>(Arc::new(Mutex::new(HashMap::new())));
+        data.insert::<ShardManagerContainer>(bot_client.shard_manager.clone());
     }
 
-    // Finally, start a single shard, and start listening to events.
+    // Serves `/metrics` and `/health`, only reachable locally unless `http_addr` says otherwise.
+    // The address was checked when the config was loaded.
+    let http_addr = config.http_addr.parse().unwrap();
+    tokio::spawn(http::serve(http_addr, bot_client.data.clone()));
+
+    // Finally, start the shards, and start listening to events.
     //
     // Shards will automatically attempt to reconnect, and will perform
     // exponential backoff until it reconnects.
-    if let Err(why) = bot_client.start().await {
-        println!("Client error: {:?}", why);
+    if let Err(why) = sharding::start(&mut bot_client, &config.shards).await {
+        error!(error = ?why, "Client error");
+    }
+}
+
+#[check]
+#[name = "CanCreate"]
+// Also runs for `help`, so people who can't create parties don't get told about `create`.
+#[check_in_help(true)]
+async fn can_create_check(
+    ctx: &Context,
+    msg: &Message,
+    _: &mut Args,
+    _: &CommandOptions
+) -> CheckResult {
+    if permissions::can_create(ctx, msg).await {
+        CheckResult::Success
+    } else {
+        CheckResult::new_user("check.can_create")
     }
 }
 
 #[command]
 #[aliases(c)]
-#[required_permissions("ADMINISTRATOR")] // Will be changed.
+#[checks(CanCreate)]
+#[description = "Creates a party with its own text and voice channel, and a role to get into them."]
+#[usage = "[players] <game> <title> [--mode <mode>] [--start <in>] [--private @member...] [--slots <name=count,...>]"]
+#[example = "4 Valorant Ranked grind"]
+#[example = "5 \"League of Legends\" Clash practice --mode draft --start 1h30m"]
+#[example = "3 Phasmophobia Spooky night --private @Casper @Wendy"]
+#[example = "WoW Mythic keys --slots tank=1,healer=1,dps=3"]
 // It asks for the amount of players and and title of the lobby.
 // It would make the title of the lobby the voice chat.
 // So it would create a new role referencing the private party.
 // So you would have to react with the emoji under it to get the role to access the private voice
 // chat for the party.
 // I can make it so people can't react to it anymore after the specified amount of players
-async fn create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let player_amount = args.single::<f64>()? as u32;
-
-    let game = match args.single::<String>() {
-        Ok(game) => game,
-        Err(_) => {
-            error_builder(ctx, msg, msg.channel_id, PartyError::NoGame).await?;
-            return Ok(())
-        }
-    };
-    let title = String::from(args.rest());
+async fn create(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    create_party(ctx, msg, args).await?;
+    Ok(())
+}
 
-    if title == "" {
-        error_builder(ctx, msg, msg.channel_id, PartyError::NoTitle).await?;
-        return Ok(())
-    }
+async fn create_party(ctx: &Context, msg: &Message, args: Args) -> Result<(), PartyError> {
+    let limits = ctx.data.read().await.get::<Config>().unwrap().party.clone();
+    let create_args = create_args::parse(args, limits.max_players)?;
+    let title = create_args.title;
 
     let guild = msg.guild_id.unwrap();
     let channel = msg.channel_id;
 
     let author = &msg.author;
 
-    if player_amount > 20 {
-        error_builder(ctx, msg, channel, PartyError::TooManyPeople).await?;
-        return Ok(())
+    let settings = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?.settings;
+
+    // Games in the catalog go by their proper name, and bring their sizes and role along.
+    let resolved = games::resolve(&settings.games, &create_args.game);
+    let (game, default_size, max_size, ping_role, game_slots) = match resolved {
+        Resolved::Found(game) => (
+            game.name.clone(),
+            game.default_size,
+            game.max_size.unwrap_or(limits.max_players).min(limits.max_players),
+            game.ping_role,
+            game.slots.clone()
+        ),
+        Resolved::Misspelt(suggestions) => {
+            return Err(PartyError::UnknownGame { game: create_args.game, suggestions })
+        },
+        Resolved::Unknown(game) => (game, None, limits.max_players, None, Vec::new())
+    };
+
+    // A party with slots is as big as its slots add up to.
+    let party_slots = create_args.slots.unwrap_or(game_slots);
+    let player_amount = if party_slots.is_empty() {
+        create_args.players.or(default_size).ok_or(PartyError::NoPlayers)?
+    } else {
+        let total = slots::total(&party_slots).ok_or(PartyError::TooManyPeople(max_size))?;
+        if create_args.players.map_or(false, |players| players != total) {
+            let slots = slots::describe(&party_slots);
+            return Err(PartyError::InvalidSlots(slots))
+        }
+        total
+    };
+
+    if player_amount > max_size {
+        return Err(PartyError::TooManyPeople(max_size))
     }
 
-    if player_amount < 2 {
-        error_builder(ctx, msg, channel, PartyError::TooLittlePeople).await?;
-        return Ok(())
+    if player_amount < limits.min_players {
+        return Err(PartyError::TooLittlePeople(limits.min_players))
     }
 
-    if DatabaseServer::party_owner(ctx, guild.0 as i64, author.id.0 as i64).await {
-        error_builder(ctx, msg, channel, PartyError::PartyOwner).await?;
-        return Ok(())
+    if DatabaseServer::party_owner(ctx, guild.0 as i64, author.id.0 as i64).await? {
+        return Err(PartyError::PartyOwner)
     }
 
-    let avatar_url: String = match author.avatar_url() {
-        Some(url) => url,
-        None => {
-            let bot_info = match ctx.http.get_current_user().await {
-                Ok(bot_info) => bot_info,
-                Err(why) => panic!("Could not get bot info: {}", why)
-            };
+    let avatar_url = avatar_url(ctx, author).await?;
 
-            bot_info.avatar_url().unwrap()
-        }
+    let (party_role_id, party_text_id, party_voice_id) =
+        create_party_channels(
+            ctx,
+            guild,
+            i18n::guild_language(&settings),
+            &author.name,
+            &title,
+            player_amount
+        ).await?;
+
+    let mut group_data = Group::new(
+        author.id.0 as i64,
+        player_amount as i64,
+        title.clone(),
+        game,
+        party_voice_id.0 as i64,
+        party_text_id.0 as i64,
+        party_role_id.0 as i64
+    ).await;
+    group_data.mode = create_args.mode;
+    group_data.starts_at = create_args.starts_at;
+    group_data.slots = party_slots;
+    if create_args.private {
+        group_data.private = true;
+        group_data.invited = create_args.invited.iter().map(|user| user.0 as i64).collect();
+    }
+
+    let ping_role = match ping_role {
+        Some(role) if subscriptions::take_ping(ctx, guild, group_data.game()).await => Some(role),
+        _ => None
     };
 
+    let embed_message = channel.send_message(&ctx.http, |cm| {
+        if let Some(ping_role) = ping_role {
+            cm.content(RoleId(ping_role as u64).mention());
+        }
+        cm.embed(|ce| party_embed(ce, &group_data, author, &avatar_url, None, &settings));
+        cm
+    }).await?;
+
+    if group_data.slots.is_empty() {
+        embed_message.react(
+            &ctx.http,
+            ReactionType::try_from(THUMBS_UP).unwrap()
+        ).await?;
+    }
+    for slot in group_data.slots.iter() {
+        embed_message.react(&ctx.http, ReactionType::try_from(slot.emoji.as_str()).unwrap()).await?;
+    }
+
+    group_data.set_messages(channel.0 as i64, msg.id.0 as i64, embed_message.id.0 as i64);
+    let party_id = group_data.role_id;
+
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    server_data.add_party(group_data.clone()).await;
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    audit_log::log(ctx, guild, &group_data, Some(author.id), PartyEvent::Created).await;
+
+    party_timer::track(ctx, guild, party_id).await;
+    spawn_party_collector(ctx, guild, party_id).await;
+
+    // There can be a lot of subscribers, and the party doesn't need to wait for them.
+    let notify_ctx = ctx.clone();
+    let link = embed_message.link();
+    tokio::spawn(async move {
+        subscriptions::notify(&notify_ctx, guild, &group_data, link).await;
+    });
+
+    Ok(())
+}
+
+// Makes the role of a new party, and the text and voice channels only that role can see.
+pub(crate) async fn create_party_channels(
+    ctx: &Context,
+    guild: GuildId,
+    language: &str,
+    owner_name: &str,
+    title: &str,
+    player_amount: u32
+) -> serenity::Result<(RoleId, ChannelId, ChannelId)> {
     let party_role = guild.create_role(&ctx.http, |er| {
-        er.name(format!("Party Group: {}", author.name))
+        er.name(tr(language, "party.role_name", &[("owner", &owner_name)]))
             .mentionable(true)
     }).await?;
 
@@ -298,170 +533,87 @@ async fn create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     ];
 
     let party_text_channel = guild.create_channel(&ctx.http, |cc| {
-            cc.name(&title)
+            cc.name(title)
                 .kind(ChannelType::Text)
                 .permissions(perms.clone())
-                .topic(format!("A Group Party created by: {}", author.name))
+                .topic(tr(language, "party.channel_topic", &[("owner", &owner_name)]))
     }).await?;
 
-    let party_text_id = party_text_channel.id;
     let party_voice_channel = guild.create_channel(&ctx.http, |cc| {
-        cc.name(&title)
+        cc.name(title)
             .kind(ChannelType::Voice)
             .user_limit(player_amount)
             .permissions(perms.clone())
     }).await?;
 
-    let party_voice_id = party_voice_channel.id;
-    let mut embed_message = channel.send_message(&ctx.http, |cm| {
-        cm.embed(|ce| {
-            let mut author_embed = CreateEmbedAuthor::default();
-            author_embed.icon_url(&avatar_url);
-            author_embed.name(&title);
-            let desc = format!("This is a party created by {}", author.name);
-            ce.description(desc);
-            ce.set_author(author_embed);
-            ce.thumbnail(&avatar_url);
-            ce.colour(Colour::DARK_GOLD);
-            ce.field("Players", "None", true);
-            ce
-        });
-        cm
-    }).await?;
-
-    embed_message.react(
-        &ctx.http,
-        ReactionType::try_from(THUMBS_UP).unwrap()
-    ).await?;
-
-    let party_owner = author.id.0 as i64;
-    let mut group_data = Group::new(
-        party_owner,
-        player_amount as i64,
-        title.clone(),
-        game,
-        party_voice_id.0 as i64,
-        party_text_id.0 as i64,
-        party_role_id.0 as i64
-    ).await;
-
-    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await;
-    server_data.add_party(group_data.clone()).await;
-    DatabaseServer::insert_or_replace(ctx, server_data.clone()).await;
+    Ok((party_role_id, party_text_channel.id, party_voice_channel.id))
+}
 
-    let mut add_reac_collector = embed_message
-        .await_reactions(&ctx)
-        .removed(true)
-        .await;
+// Collects the reactions on a party's announcement in the background. Only one collector runs
+// per party, so asking again for a party that is already being collected (e.g. after the shard
+// reconnects) does nothing.
+pub(crate) async fn spawn_party_collector(ctx: &Context, guild: GuildId, party_id: i64) {
+    if !sharding::owns_guild(ctx, guild).await {
+        return
+    }
 
-    let ctx1 = ctx.clone();
-    let msg1 = msg.clone();
-    let msg2 = embed_message.clone();
+    let collectors = ctx.data.read().await.get::<PartyCollectors>().unwrap().clone();
+    if !collectors.lock().await.insert(party_id) {
+        return
+    }
 
+    let ctx = ctx.clone();
     tokio::spawn(async move {
-        handle_party_timer(&ctx1, guild, &party_owner, channel.0, &msg1, &msg2).await;
+        if let Err(why) = collect_party_reactions(&ctx, guild, party_id).await {
+            metrics::record_error(&*why);
+            error!(party = party_id, error = ?why, "Stopped collecting party reactions");
+        }
+
+        collectors.lock().await.remove(&party_id);
     });
+}
 
-    while let Some(action) = add_reac_collector.next().await {
-        let user_id = &action
-            .as_inner_ref()
-            .user_id
-            .unwrap();
-        let user = user_id.to_user(&ctx.http).await?;
-        let id = user_id.0;
-        let emoji = &action.as_inner_ref().emoji;
-
-        if group_data.full() {
-            &ctx.http.delete_reaction(
-                channel.0,
-                msg.id.0,
-                Some(user.id.0),
-                emoji
-            ).await?;
+#[instrument(skip(ctx))]
+async fn collect_party_reactions(ctx: &Context, guild: GuildId, party_id: i64) -> CommandResult {
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let group = match server_data.get_party(&party_id).await {
+        Some(group) if group.has_announcement() => group,
+        _ => return Ok(())
+    };
 
-            continue
-        }
+    let channel = group.channel_id as u64;
+    let message = group.message_id as u64;
 
-        match emoji.as_data().as_str() {
-            THUMBS_UP => {
-                if action.is_added() && !group_data.in_player_vec(&(id as i64)) {
-                    group_data.add_player(id as i64).await;
-                    group_data.add_player_name(user.name.clone()).await;
-                    &ctx.http.add_member_role(
-                        guild.0,
-                        id as u64,
-                        party_role_id.0
-                    );
-                    let mut member = guild.member(
-                        &ctx.http,
-                        id as u64
-                    ).await?;
-                    member.add_role(&ctx.http, party_role_id).await?;
-                    server_data.edit_party(&party_owner, group_data.clone()).await;
-                    DatabaseServer::insert_or_replace(ctx, server_data.clone()).await;
-                    embed_message.edit(&ctx.http, |em| {
-                        em.embed(|ce| {
-                            let mut author_embed = CreateEmbedAuthor::default();
-                            author_embed.icon_url(&avatar_url);
-                            author_embed.name(&title);
-                            let desc = format!("This is a party created by {}", author.name);
-                            ce.description(desc);
-                            ce.set_author(author_embed);
-                            ce.thumbnail(&avatar_url);
-                            ce.colour(Colour::DARK_GOLD);
-                            ce.field(
-                                "Players",
-                                group_data.players(),
-                                true
-                            );
-                            ce
-                        });
-                        em
-                    }).await?;
-                } else if action.is_removed() && group_data.in_player_vec(&(id as i64)) {
-                    group_data.remove_player(id as i64).await;
-                    group_data.remove_player_name(user.name.clone()).await;
-                    &ctx.http.remove_member_role(
-                        guild.0,
-                        id as u64,
-                        party_role_id.0
-                    );
-                    let mut member = guild.member(
-                        &ctx.http,
-                        id as u64
-                    ).await?;
-                    member.remove_role(&ctx.http, party_role_id).await?;
-                    server_data.edit_party(&party_owner, group_data.clone()).await;
-                    DatabaseServer::insert_or_replace(ctx, server_data.clone()).await;
-
-                    embed_message.edit(&ctx.http, |em| {
-                        em.embed(|ce| {
-                            let mut author_embed = CreateEmbedAuthor::default();
-                            author_embed.icon_url(&avatar_url);
-                            author_embed.name(&title);
-                            let desc = format!("This is a party created by {}", author.name);
-                            ce.description(desc);
-                            ce.set_author(author_embed);
-                            ce.thumbnail(&avatar_url);
-                            ce.colour(Colour::DARK_GOLD);
-                            ce.field(
-                                "Players",
-                                group_data.players(),
-                                true
-                            );
-                            ce
-                        });
-                        em
-                    }).await?;
+    let mut add_reac_collector = ReactionCollectorBuilder::new(ctx)
+        .channel_id(channel)
+        .message_id(message)
+        .removed(true)
+        .await;
+    let _collecting = metrics::CollectorGuard::new();
+
+    loop {
+        let action = match tokio::time::timeout(COLLECTOR_CHECK, add_reac_collector.next()).await {
+            Ok(Some(action)) => action,
+            Ok(None) => break,
+            // Nobody reacted in a while, stop if the party is gone.
+            Err(_) => {
+                match DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await {
+                    Ok(server_data) if server_data.get_party(&party_id).await.is_none() => break,
+                    Ok(_) => {},
+                    Err(why) => error!(party = party_id, error = ?why, "Could not check on the party")
                 }
-            },
-            _ => {
-                &ctx.http.delete_reaction(
-                    channel.0,
-                    msg.id.0,
-                    Some(user.id.0),
-                    emoji
-                ).await?;
+
+                continue
+            }
+        };
+
+        match handle_party_reaction(ctx, guild, party_id, &action).await {
+            Ok(true) => {},
+            Ok(false) => break,
+            // One reaction going wrong shouldn't stop the party from taking any more.
+            Err(why) => {
+                metrics::record_error(&*why);
+                error!(error = ?why, party = party_id, "Could not handle a party reaction");
             }
         }
     }
@@ -469,165 +621,802 @@ async fn create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     Ok(())
 }
 
-async fn handle_party_timer(
+// Joins or leaves the party for a single reaction on its announcement. Returns false once the
+// party is gone and there is nothing left to collect.
+async fn handle_party_reaction(
     ctx: &Context,
     guild: GuildId,
-    owner: &i64,
-    channel_id: u64,
-    user_message: &Message,
-    bot_message: &Message
-) -> CommandResult {
-    let mut timer = tokio::time::interval(Duration::from_secs(60));
+    party_id: i64,
+    action: &ReactionAction
+) -> CommandResult<bool> {
+    let user_id = action
+        .as_inner_ref()
+        .user_id
+        .unwrap();
+    // The bot's own reactions come through too, no need to ask Discord who that is.
+    if user_id == ctx.cache.current_user_id().await {
+        return Ok(true)
+    }
 
-    loop {
-        timer.tick().await;
+    let user = user_id.to_user(ctx).await?;
+    let id = user_id.0;
+    let emoji = &action.as_inner_ref().emoji;
 
-        let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await;
-        let mut group = match server_data.get_party(owner).await {
-            Some(group) => group,
-            None => break
-        };
+    if user.bot {
+        return Ok(true)
+    }
 
-        if group.time_til_auto_del > 0 && group.player_amount() < 2 {
-            group.time_til_auto_del -= 1;
-            if group.time_til_auto_del == 0 {
-                ctx.http.delete_channel(group.text_id as u64).await;
-                ctx.http.delete_channel(group.voice_id as u64).await;
-                ctx.http.delete_role(guild.0, group.role_id as u64).await;
-                ctx.http.delete_message(channel_id, user_message.id.0).await;
-                ctx.http.delete_message(channel_id, bot_message.id.0).await;
-                server_data.delete_party(owner).await;
-                DatabaseServer::insert_or_replace(ctx, server_data.clone()).await;
-                break
+    // The party can be changed outside of this collector (e.g. a member leaving the guild),
+    // so always work on the latest copy from the database.
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let mut group_data = match server_data.get_party(&party_id).await {
+        Some(group) => group,
+        None => return Ok(false)
+    };
+    let channel = group_data.channel_id as u64;
+    let message = group_data.message_id as u64;
+    let party_role_id = RoleId(group_data.role_id as u64);
+
+    // Nobody can join a full party, and only the people invited can join a private one.
+    let player = group_data.in_player_vec(&(id as i64));
+    if action.is_added() && !player && (group_data.full() || !group_data.can_join(id as i64)) {
+        ctx.http.delete_reaction(
+            channel,
+            message,
+            Some(user.id.0),
+            emoji
+        ).await?;
+
+        return Ok(true)
+    }
+
+    // Whether the reaction makes them join or leave the party. Any other reaction gets taken
+    // back.
+    let emoji_data = emoji.as_data();
+    let joins = match group_data.slot_index(&emoji_data) {
+        Some(slot) if action.is_added() => {
+            // One slot each, and a full slot stays full even while others are still open.
+            // Members matched through the lfg queue are in the party before they have a slot.
+            let has_slot = group_data.has_slot(id as i64);
+            if has_slot || !group_data.join_slot(slot, id as i64) {
+                ctx.http.delete_reaction(
+                    channel,
+                    message,
+                    Some(user.id.0),
+                    emoji
+                ).await?;
+
+                return Ok(true)
+            }
+
+            if player {
+                server_data.edit_party(&party_id, group_data.clone()).await;
+                DatabaseServer::insert_or_replace(ctx, server_data).await?;
+                update_party_embed(ctx, guild, &group_data).await?;
+                return Ok(true)
+            }
+            Some(true)
+        },
+        Some(slot) => if group_data.in_slot(slot, id as i64) { Some(false) } else { None },
+        // Parties with slots only get joined through them.
+        None if emoji_data == THUMBS_UP && group_data.slots.is_empty() => {
+            if action.is_added() && !group_data.in_player_vec(&(id as i64)) {
+                Some(true)
+            } else if action.is_removed() && group_data.in_player_vec(&(id as i64)) {
+                Some(false)
             } else {
-                server_data.edit_party(owner, group).await;
-                DatabaseServer::insert_or_replace(ctx, server_data.clone()).await;
+                None
             }
-        } else if group.player_amount() > 2 {
-            loop {
-                timer.tick().await;
-                let server_data = DatabaseServer::get_or_insert_new(
-                    ctx,
-                    guild.0 as i64,
-                    None).await;
-                let group = match server_data.get_party(owner).await {
-                    Some(group) => group,
-                    None => break
-                };
-
-                if group.player_amount() < 2 {
-                    break
-                }
+        },
+        None => {
+            if action.is_added() {
+                ctx.http.delete_reaction(
+                    channel,
+                    message,
+                    Some(user.id.0),
+                    emoji
+                ).await?;
             }
+            None
         }
+    };
+
+    match joins {
+        Some(true) => {
+            group_data.add_player(id as i64).await;
+            group_data.add_player_name(user.name.clone()).await;
+            let mut member = guild.member(
+                &ctx.http,
+                id as u64
+            ).await?;
+            member.add_role(&ctx.http, party_role_id).await?;
+            server_data.edit_party(&party_id, group_data.clone()).await;
+            DatabaseServer::insert_or_replace(ctx, server_data).await?;
+            update_party_embed(ctx, guild, &group_data).await?;
+            metrics::PARTY_JOINS.inc();
+            audit_log::log(ctx, guild, &group_data, Some(user.id), PartyEvent::Joined(user.id)).await;
+        },
+        Some(false) => {
+            group_data.remove_player(id as i64).await;
+            let mut member = guild.member(
+                &ctx.http,
+                id as u64
+            ).await?;
+            member.remove_role(&ctx.http, party_role_id).await?;
+            server_data.edit_party(&party_id, group_data.clone()).await;
+            DatabaseServer::insert_or_replace(ctx, server_data).await?;
+            update_party_embed(ctx, guild, &group_data).await?;
+            metrics::PARTY_LEAVES.inc();
+            audit_log::log(ctx, guild, &group_data, Some(user.id), PartyEvent::Left(user.id)).await;
+        },
+        None => {}
     }
 
+    Ok(true)
+}
+
+#[command]
+#[aliases(d)]
+#[description = "Deletes the party you own."]
+// Deletes the party the author owns, archiving it first if the server wants it.
+async fn disband(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let author_id = msg.author.id.0 as i64;
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+
+    let group = match server_data.parties.iter().find(|party| party.owner == author_id) {
+        Some(group) => group.clone(),
+        None => return Err(PartyError::NotPartyOwner.into())
+    };
+
+    audit_log::log(ctx, guild, &group, Some(msg.author.id), PartyEvent::Disbanded).await;
+    party_timer::disband(ctx, guild, &group).await?;
+    msg.delete(&ctx.http).await?;
+
     Ok(())
 }
 
-async fn error_builder(
-    ctx: &Context,
-    orginial_msg: &Message,
-    channel: ChannelId,
-    error: PartyError
-) -> CommandResult {
-    let error_msg = channel.send_message(&ctx.http, |cm| {
+#[command]
+#[description = "Takes a member out of the party you own. They can join again by reacting again."]
+#[usage = "<@member>"]
+#[example = "@Casper"]
+#[num_args(1)]
+async fn kick(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let author_id = msg.author.id.0 as i64;
+    let language = i18n::language_for(ctx, msg).await;
+    let kicked = args.single::<UserId>()?;
+    let id = kicked.0 as i64;
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+
+    let mut group = match server_data.parties.iter().find(|party| party.owner == author_id) {
+        Some(group) => group.clone(),
+        None => return Err(PartyError::NotPartyOwner.into())
+    };
+
+    if kicked == msg.author.id || !group.in_player_vec(&id) {
+        let reply = tr(&language, "kick.not_member", &[("member", &kicked.mention())]);
+        msg.channel_id.say(&ctx.http, reply).await?;
+        return Ok(())
+    }
+
+    // The reaction they joined with, which has to go too or they couldn't join again. Members
+    // matched through the lfg queue might not have picked a slot yet.
+    let emoji = match group.slots.iter().find(|slot| slot.members.contains(&id)) {
+        Some(slot) => Some(slot.emoji.clone()),
+        None if group.slots.is_empty() => Some(THUMBS_UP.to_string()),
+        None => None
+    };
+
+    group.remove_player(id).await;
+    server_data.edit_party(&group.role_id, group.clone()).await;
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+
+    guild.member(&ctx.http, kicked).await?.remove_role(&ctx.http, group.role_id as u64).await?;
+    if let (true, Some(emoji)) = (group.has_announcement(), emoji) {
+        ctx.http.delete_reaction(
+            group.channel_id as u64,
+            group.message_id as u64,
+            Some(kicked.0),
+            &ReactionType::try_from(emoji.as_str()).unwrap()
+        ).await?;
+    }
+    update_party_embed(ctx, guild, &group).await?;
+    metrics::PARTY_LEAVES.inc();
+    audit_log::log(ctx, guild, &group, Some(msg.author.id), PartyEvent::Kicked(kicked)).await;
+
+    msg.channel_id.say(&ctx.http, tr(&language, "kick.kicked", &[("member", &kicked.mention())])).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Changes the title or the mode of the party you own."]
+#[usage = "title <title> | mode <mode|off>"]
+#[example = "title Late night ranked"]
+#[example = "mode draft"]
+#[min_args(2)]
+async fn edit(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let author_id = msg.author.id.0 as i64;
+    let language = i18n::language_for(ctx, msg).await;
+    let field = args.single::<String>()?;
+    let value = args.rest().trim().to_string();
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+
+    let mut group = match server_data.parties.iter().find(|party| party.owner == author_id) {
+        Some(group) => group.clone(),
+        None => return Err(PartyError::NotPartyOwner.into())
+    };
+
+    let (field, value) = match field.as_str() {
+        "title" => {
+            // The channels are named after the party, so they follow it.
+            ChannelId(group.text_id as u64).edit(&ctx.http, |ec| ec.name(&value)).await?;
+            ChannelId(group.voice_id as u64).edit(&ctx.http, |ec| ec.name(&value)).await?;
+            group.set_title(value.clone());
+            ("title", Some(value))
+        },
+        "mode" => {
+            group.mode = if value == "off" { None } else { Some(value) };
+            ("mode", group.mode.clone())
+        },
+        _ => {
+            msg.channel_id.say(&ctx.http, tr(&language, "edit.unknown_field", &[("field", &field)])).await?;
+            return Ok(())
+        }
+    };
+
+    server_data.edit_party(&group.role_id, group.clone()).await;
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    update_party_embed(ctx, guild, &group).await?;
+    audit_log::log(ctx, guild, &group, Some(msg.author.id), PartyEvent::Edited { field, value }).await;
+
+    msg.channel_id.say(&ctx.http, tr(&language, "edit.edited", &[])).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases(i)]
+#[description = "Shows who is in a party and their rank in its game. Used in a party's channel it shows that party, anywhere else the one you are in."]
+async fn info(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let author_id = msg.author.id.0 as i64;
+    let language = i18n::language_for(ctx, msg).await;
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+
+    let group = server_data.parties
+        .iter()
+        .find(|party| party.text_id == msg.channel_id.0 as i64)
+        .or_else(|| server_data.parties
+            .iter()
+            .find(|party| party.owner == author_id || party.in_player_vec(&author_id)));
+    let group = match group {
+        Some(group) => group,
+        None => {
+            msg.channel_id.say(&ctx.http, tr(&language, "info.no_party", &[])).await?;
+            return Ok(())
+        }
+    };
+
+    let members: Vec<String> = group.player_ids()
+        .iter()
+        .map(|&player| {
+            let player = UserId(player as u64);
+            match ranks::rating(&server_data.settings, player, group.game()) {
+                Some(rating) => format!("{} · **{}**", player.mention(), rating),
+                None => format!("{} · {}", player.mention(), tr(&language, "info.unrated", &[]))
+            }
+        })
+        .collect();
+
+    msg.channel_id.send_message(&ctx.http, |cm| {
         cm.embed(|ce| {
-            ce.title(format!("{}", error));
-            ce.colour(Colour::RED);
+            ce.title(group.title());
+            ce.field(tr(&language, "party.game", &[]), group.game(), true);
+            ce.field(tr(&language, "info.owner", &[]), UserId(group.owner as u64).mention(), true);
+            ce.field(
+                tr(&language, "party.slots", &[]),
+                format!("{}/{}", group.player_amount(), group.max_player_amount()),
+                true
+            );
+            ce.field(tr(&language, "party.players", &[]), if members.is_empty() {
+                tr(&language, "party.nobody", &[])
+            } else {
+                members.join("\n")
+            }, false);
+            ce.colour(Colour::DARK_GOLD);
             ce
         });
         cm
     }).await?;
 
-    tokio::time::delay_for(Duration::from_secs(20)).await;
-    error_msg.delete(&ctx.http).await?;
-    orginial_msg.delete(&ctx.http).await?;
-
     Ok(())
 }
 
-#[command]
-async fn stop(_ctx: &Context, _msg: &Message, mut _args: Args) -> CommandResult {
-    // TODO: Make the bot turn off
-    // TODO: Remove all parties/groups from database, and in the server.
-    unimplemented!()
+// Takes a member that left or got banned out of every party in the guild. If they owned a party,
+// it gets handed to another player, or disbanded when there is nobody left to take it.
+#[instrument(skip(ctx, user), fields(user = %user.id))]
+async fn remove_member_from_parties(ctx: &Context, guild: GuildId, user: &User, banned: bool) {
+    let removals = ctx.data.read().await.get::<MemberRemovals>().unwrap().clone();
+    let _removing = removals.lock().await;
+
+    let id = user.id.0 as i64;
+    let mut server_data = match DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await {
+        Ok(server_data) => server_data,
+        Err(why) => {
+            error!(user = %user.id, error = ?why, "Could not load the parties to take a member out of");
+            return
+        }
+    };
+    let mut updated = Vec::new();
+    let mut disbanded = Vec::new();
+    // How many members are out of a party now. Owning a party isn't the same as being in it, and a
+    // party that gets disbanded takes everyone still in it along.
+    let mut left = 0;
+
+    for group in server_data.parties.iter() {
+        if group.owner != id && !group.in_player_vec(&id) {
+            continue
+        }
+
+        if group.in_player_vec(&id) {
+            left += 1;
+        }
+
+        let mut group = group.clone();
+        group.remove_player(id).await;
+
+        if group.owner != id {
+            updated.push((group, None));
+            continue
+        }
+
+        match group.transfer_ownership(&server_data.owners()) {
+            Some(new_owner) => updated.push((group, Some(UserId(new_owner as u64)))),
+            None => {
+                left += group.player_amount();
+                disbanded.push(group)
+            }
+        }
+    }
+
+    if updated.is_empty() && disbanded.is_empty() {
+        return
+    }
+
+    metrics::PARTY_LEAVES.inc_by(left as i64);
+
+    for (group, _) in updated.iter() {
+        server_data.edit_party(&group.role_id, group.clone()).await;
+    }
+    // Nothing on Discord changes when the parties couldn't be saved, or they would disagree.
+    if let Err(why) = DatabaseServer::insert_or_replace(ctx, server_data).await {
+        error!(user = %user.id, error = ?why, "Could not take a member out of their parties");
+        return
+    }
+
+    for (group, new_owner) in updated.iter() {
+        if let Err(why) = update_party_embed(ctx, guild, group).await {
+            metrics::record_error(&*why);
+            error!(party = group.role_id, error = ?why, "Could not update party embed");
+        }
+
+        audit_log::log(ctx, guild, group, None, PartyEvent::Removed { member: user.id, banned }).await;
+        if let Some(new_owner) = new_owner {
+            let event = PartyEvent::OwnershipTransferred { from: user.id, to: *new_owner };
+            audit_log::log(ctx, guild, group, None, event).await;
+        }
+    }
+
+    for group in disbanded.iter() {
+        audit_log::log(ctx, guild, group, None, PartyEvent::Removed { member: user.id, banned }).await;
+        audit_log::log(ctx, guild, group, None, PartyEvent::Disbanded).await;
+        if let Err(why) = party_timer::disband(ctx, guild, group).await {
+            error!(party = group.role_id, error = ?why, "Could not disband party");
+        }
+    }
+}
+
+// Runs every message through the standard framework inside a span of its own, so the hooks and
+// whatever command runs all log under it. Spans can't be carried from the `before` hook into a
+// command, which is why this wraps the framework instead. `before` fills in the command.
+struct TracedFramework(StandardFramework);
+
+#[async_trait]
+impl Framework for TracedFramework {
+    async fn dispatch(&self, ctx: Context, msg: Message) {
+        let span = info_span!(
+            "command",
+            command = field::Empty,
+            guild = ?msg.guild_id,
+            user = %msg.author.id,
+            message = %msg.id
+        );
+        self.0.dispatch(ctx, msg).instrument(span).await
+    }
 }
 
 #[hook]
 async fn before(ctx: &Context, msg: &Message, command_name: &str) -> bool {
-    println!("Got command '{}' by user '{}'", command_name, msg.author.name);
-
-    // Increment the number of times this command has been run once. If
-    // the command's name does not exist in the counter, add a default
-    // value of 0.
-    let mut data = ctx.data.write().await;
-    let counter = data.get_mut::<CommandCounter>().expect("Expected CommandCounter in TypeMap.");
-    let entry = counter.entry(command_name.to_string()).or_insert(0);
-    *entry += 1;
+    Span::current().record("command", &command_name);
+    info!(
+        command = command_name,
+        guild = ?msg.guild_id,
+        user = %msg.author.id,
+        "Got command"
+    );
+
+    // The metric starts over on every restart, the count in the database doesn't.
+    metrics::COMMANDS_RUN.with_label_values(&[command_name]).inc();
+    if let Some(guild) = msg.guild_id {
+        if let Err(why) = DatabaseServer::increment_command(ctx, guild.0 as i64, command_name).await {
+            warn!(guild = %guild, error = ?why, "Could not count command");
+        }
+    }
 
     true // if `before` returns false, command processing doesn't happen.
 }
 
 #[hook]
-async fn after(_ctx: &Context, _msg: &Message, command_name: &str, command_result: CommandResult) {
-    match command_result {
-        Ok(()) => println!("Processed command '{}'", command_name),
-        Err(why) => println!("Command '{}' returned error {:?}", command_name, why),
+async fn after(ctx: &Context, msg: &Message, command_name: &str, command_result: CommandResult) {
+    let why = match command_result {
+        Ok(()) => {
+            info!(command = command_name, "Processed command");
+            return
+        },
+        Err(why) => why
+    };
+
+    metrics::COMMANDS_FAILED.with_label_values(&[command_name]).inc();
+    metrics::record_error(&*why);
+
+    // Mistakes get explained to whoever made them, anything else only says that something broke
+    // and gets logged in full.
+    let language = i18n::language_for(ctx, msg).await;
+    let (title, hint) = match why.downcast_ref::<PartyError>() {
+        Some(error) if error.is_user_error() => {
+            debug!(command = command_name, error = %error, "Command was used wrong");
+            (error.message(&language), error.usage_hint(&language))
+        },
+        Some(error) => {
+            error!(command = command_name, error = %error, source = ?error.source(), "Command failed");
+            (error.message(&language), None)
+        },
+        // Commands that aren't about parties hand database errors up as they are.
+        None if why.is::<mongodb::error::Error>() => {
+            error!(command = command_name, error = ?why, "Command failed");
+            (tr(&language, "error.database", &[]), None)
+        },
+        // Whatever it says could be anything, so it only goes in the log.
+        None => {
+            error!(command = command_name, error = ?why, "Command returned an error");
+            (tr(&language, "error.generic", &[]), None)
+        }
+    };
+
+    if let Err(why) = error::report(ctx, msg, title, hint).await {
+        metrics::record_error(&why);
+        warn!(error = ?why, "Could not report a command error");
     }
 }
 
 #[hook]
 async fn unknown_command(_ctx: &Context, _msg: &Message, unknown_command_name: &str) {
-    println!("Could not find command named '{}'", unknown_command_name);
+    debug!(command = unknown_command_name, "Could not find command");
 }
 
 #[hook]
 async fn normal_message(_ctx: &Context, msg: &Message) {
-    println!("Message is not a command '{}'", msg.content);
+    // Only ever log where the message is, never what it says.
+    trace!(message = %msg.id, channel = %msg.channel_id, "Message is not a command");
 }
 
 #[hook]
 async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
-    if let DispatchError::Ratelimited(duration) = error {
-        let _ = msg
-            .channel_id
-            .say(&ctx.http, &format!("Try this again in {} seconds.", duration.as_secs()))
-            .await;
+    let language = i18n::language_for(ctx, msg).await;
+    let message = match error {
+        DispatchError::CheckFailed(check, reason) => match reason {
+            Reason::User(user) => tr(&language, &user, &[]),
+            Reason::UserAndLog { user, log } => {
+                warn!(check, reason = %log, "Check failed");
+                tr(&language, &user, &[])
+            },
+            Reason::Log(log) => {
+                warn!(check, reason = %log, "Check failed");
+                tr(&language, "dispatch.check_failed", &[])
+            },
+            _ => tr(&language, "dispatch.check_failed", &[])
+        },
+        DispatchError::Ratelimited(duration) => {
+            tr(&language, "dispatch.ratelimited", &[("seconds", &duration.as_secs())])
+        },
+        DispatchError::CommandDisabled(command) => {
+            tr(&language, "dispatch.command_disabled", &[("command", &command)])
+        },
+        DispatchError::BlockedUser => tr(&language, "dispatch.blocked_user", &[]),
+        DispatchError::BlockedGuild => tr(&language, "dispatch.blocked_guild", &[]),
+        DispatchError::BlockedChannel => tr(&language, "dispatch.blocked_channel", &[]),
+        DispatchError::OnlyForDM => tr(&language, "dispatch.only_for_dm", &[]),
+        DispatchError::OnlyForGuilds => tr(&language, "dispatch.only_for_guilds", &[]),
+        DispatchError::OnlyForOwners => tr(&language, "dispatch.only_for_owners", &[]),
+        DispatchError::LackingRole => tr(&language, "dispatch.lacking_role", &[]),
+        DispatchError::LackingPermissions(permissions) => {
+            tr(&language, "dispatch.lacking_permissions", &[("permissions", &permission_names(permissions))])
+        },
+        DispatchError::NotEnoughArguments { min, given } => {
+            tr(&language, "dispatch.not_enough_arguments", &[("min", &min), ("given", &given)])
+        },
+        DispatchError::TooManyArguments { max, given } => {
+            tr(&language, "dispatch.too_many_arguments", &[("max", &max), ("given", &given)])
+        },
+        // Answering bots and webhooks only starts conversations nobody wants.
+        _ => return
+    };
+
+    debug!(message = %msg.id, "Command could not be dispatched");
+    if let Err(why) = error::report(ctx, msg, message, None).await {
+        metrics::record_error(&why);
+        warn!(error = ?why, "Could not report a dispatch error");
     }
 }
 
+// `MANAGE_GUILD | ADMINISTRATOR` reads as "Manage Guild, Administrator".
+fn permission_names(permissions: Permissions) -> String {
+    format!("{:?}", permissions)
+        .split(" | ")
+        .map(|name| {
+            name.split('_')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) => first.to_string() + &chars.as_str().to_lowercase(),
+                        None => String::new()
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 // The framework provides two built-in help commands for you to use.
 // But you can also make your own customized help command that forwards
 // to the behaviour of either of them.
 #[help]
-// This replaces the information that a user can pass
-// a command-name as argument to gain specific information about it.
-#[individual_command_tip =
-"Hello!\n\n
-If you want more information about a specific command, just pass the command as argument."]
-// Some arguments require a `{}` in order to replace it with contextual information.
-// In this case our `{}` refers to a command's name.
-#[command_not_found_text = "Could not find: `{}`."]
-// On another note, you can set up the help-menu-filter-behaviour.
+// Everything the help says is written by `send_help`, in the reader's language.
+// The help-menu-filter-behaviour can still be set up here.
 // Here are all possible settings shown on all possible options.
 // First case is if a user lacks permissions for a command, we can hide the command.
 #[lacking_permissions = "Hide"]
 // If the user is nothing but lacking a certain role, we just display it hence our variant is
 // `Nothing`.
 #[lacking_role = "Nothing"]
-// Serenity will automatically analyse and generate a hint/tip explaining the possible
-// cases of ~~strikethrough-commands~~, but only if
-// `strikethrough_commands_tip_in_{dm, guild}` aren't specified.
-// If you pass in a value, it will be displayed instead.
+// Commands whose checks fail, like `create` for people without a party creator role, get hidden
+// too.
+#[lacking_conditions = "Hide"]
+// `help dm` sends the help to the author instead of the channel, and so does a busy channel.
 async fn my_help(
     context: &Context,
     msg: &Message,
-    args: Args,
+    mut args: Args,
     help_options: &'static HelpOptions,
     groups: &[&'static CommandGroup],
     owners: HashSet<UserId>
 ) -> CommandResult {
-    let _ = with_embeds(context, msg, args, help_options, groups, owners).await;
+    let dm = if args.current() == Some("dm") {
+        args.advance();
+        true
+    } else {
+        channel_busy(context, msg).await
+    };
+    let args = Args::new(args.rest(), &[Delimiter::Single(' ')]);
+
+    if dm {
+        let dm_channel = msg.author.create_dm_channel(context).await?;
+        if send_help(context, msg, dm_channel.id, &args, help_options, groups, &owners).await.is_ok() {
+            msg.react(context, '📬').await?;
+            return Ok(())
+        }
+        // Their DMs are closed, so the channel will have to do.
+    }
+
+    send_help(context, msg, msg.channel_id, &args, help_options, groups, &owners).await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+// How many messages within `BUSY_WINDOW_SECS` make a channel too busy for the help to be posted in.
+const BUSY_MESSAGES: u64 = 10;
+const BUSY_WINDOW_SECS: i64 = 60;
+
+async fn channel_busy(ctx: &Context, msg: &Message) -> bool {
+    let messages = match msg.channel_id
+        .messages(&ctx.http, |retriever| retriever.before(msg.id).limit(BUSY_MESSAGES))
+        .await
+    {
+        Ok(messages) => messages,
+        Err(_) => return false
+    };
+
+    // Newest first, so the last one is the oldest.
+    match messages.last() {
+        Some(oldest) if messages.len() as u64 == BUSY_MESSAGES => {
+            (msg.timestamp - oldest.timestamp).num_seconds() < BUSY_WINDOW_SECS
+        },
+        _ => false
+    }
+}
+
+// Does what `with_embeds` does, except to any channel and in the reader's language. Without a
+// command to look up, the overview is followed by how to use the party commands.
+async fn send_help(
+    ctx: &Context,
+    msg: &Message,
+    channel: ChannelId,
+    args: &Args,
+    help_options: &'static HelpOptions,
+    groups: &[&'static CommandGroup],
+    owners: &HashSet<UserId>
+) -> serenity::Result<()> {
+    let language = i18n::language_for(ctx, msg).await;
+    let help = create_customised_help_data(ctx, msg, args, groups, owners, help_options).await;
+    match &help {
+        CustomisedHelpData::SuggestedCommands { suggestions, .. } => {
+            let suggestions = suggestions.join("`, `");
+            let text = tr(&language, "help.suggestions", &[("suggestions", &suggestions)]);
+            send_error_embed(&ctx.http, channel, &text, help_options.embed_error_colour).await?;
+        },
+        CustomisedHelpData::NoCommandFound { .. } => {
+            let text = tr(&language, "help.not_found", &[("command", &args.message())]);
+            send_error_embed(&ctx.http, channel, &text, help_options.embed_error_colour).await?;
+        },
+        CustomisedHelpData::GroupedCommands { groups, .. } => {
+            channel.send_message(&ctx.http, |cm| {
+                cm.embed(|ce| {
+                    ce.description(tr(&language, "help.tip", &[]));
+                    for group in groups {
+                        let mut text = String::new();
+                        let key = format!("groups.{}", group.name.to_lowercase());
+                        if let Some(summary) = i18n::tr_or(&language, &key, group.summary) {
+                            text.push_str(&format!("*{}*\n", summary));
+                        }
+                        if !group.prefixes.is_empty() {
+                            let prefixes = group.prefixes.join("`, `");
+                            text.push_str(&tr(&language, "help.group_prefix", &[("prefixes", &prefixes)]));
+                            text.push('\n');
+                        }
+                        text.push_str(&group.command_names.join("\n"));
+                        ce.field(group.name, text, true);
+                    }
+                    ce.colour(help_options.embed_success_colour);
+                    ce
+                });
+                cm
+            }).await?;
+            send_party_usage(ctx, msg, channel).await?;
+        },
+        // The framework found it, so looking it up again can't miss.
+        CustomisedHelpData::SingleCommand { .. } => {
+            if let Some((group, options)) = find_command(groups, args.message()) {
+                send_command_help(ctx, msg, channel, &language, group, options, help_options).await?;
+            }
+        },
+        _ => {}
+    }
+
+    Ok(())
+}
+
+// The command the help was asked about, like `create` or `party create`, and the group it is in.
+fn find_command(
+    groups: &[&'static CommandGroup],
+    name: &str
+) -> Option<(&'static CommandGroup, &'static CommandOptions)> {
+    let name = name.split_whitespace().last()?;
+    groups.iter().find_map(|group| {
+        group.options.commands
+            .iter()
+            .find(|command| command.options.names.iter().any(|alias| *alias == name))
+            .map(|command| (*group, command.options))
+    })
+}
+
+async fn send_command_help(
+    ctx: &Context,
+    msg: &Message,
+    channel: ChannelId,
+    language: &str,
+    group: &'static CommandGroup,
+    options: &'static CommandOptions,
+    help_options: &'static HelpOptions
+) -> serenity::Result<()> {
+    let prefix = prefix::prefix_for(ctx, msg.guild_id).await;
+    let name = options.names[0];
+    let invocation = match group.options.prefixes.first() {
+        Some(group_prefix) => format!("{}{} {}", prefix, group_prefix, name),
+        None => format!("{}{}", prefix, name)
+    };
+    let description = i18n::tr_or(language, &format!("commands.{}", name), options.desc);
+    let usage = i18n::tr_or(language, &format!("usages.{}", name), options.usage);
+
+    channel.send_message(&ctx.http, |cm| {
+        cm.embed(|ce| {
+            ce.title(name);
+            if let Some(description) = description {
+                ce.description(description);
+            }
+            ce.field(tr(language, "help.usage", &[]), match usage {
+                Some(usage) => format!("`{} {}`", invocation, usage),
+                None => format!("`{}`", invocation)
+            }, false);
+            if !options.examples.is_empty() {
+                let examples: Vec<String> = options.examples
+                    .iter()
+                    .map(|example| format!("`{} {}`", invocation, example))
+                    .collect();
+                ce.field(tr(language, "help.examples", &[]), examples.join("\n"), false);
+            }
+            if options.names.len() > 1 {
+                ce.field(tr(language, "help.aliases", &[]), options.names[1..].join(", "), true);
+            }
+            ce.field(tr(language, "help.group", &[]), group.name, true);
+            ce.colour(help_options.embed_success_colour);
+            ce
+        });
+        cm
+    }).await?;
+
+    Ok(())
+}
+
+// The built-in help doesn't know about the guild's prefix, so this spells out every party command
+// the author can use, with the prefix they have to type here.
+async fn send_party_usage(ctx: &Context, msg: &Message, channel: ChannelId) -> serenity::Result<()> {
+    let prefix = prefix::prefix_for(ctx, msg.guild_id).await;
+    let language = i18n::language_for(ctx, msg).await;
+    let group_prefix = PARTY_GROUP.options.prefixes[0];
+
+    let mut usages = Vec::new();
+    for command in PARTY_GROUP.options.commands {
+        let options = command.options;
+
+        let mut allowed = true;
+        for check in options.checks.iter().filter(|check| check.check_in_help) {
+            let mut no_args = Args::new("", &[]);
+            if !(check.function)(ctx, msg, &mut no_args, options).await.is_success() {
+                allowed = false;
+            }
+        }
+        if !allowed {
+            continue
+        }
+
+        let invocation = format!("{}{} {}", prefix, group_prefix, options.names[0]);
+        let usage = i18n::tr_or(&language, &format!("usages.{}", options.names[0]), options.usage);
+        let mut text = match usage {
+            Some(usage) => format!("`{} {}`", invocation, usage),
+            None => format!("`{}`", invocation)
+        };
+        for example in options.examples {
+            text.push_str(&format!("\n{} `{} {}`", tr(&language, "help.example", &[]), invocation, example));
+        }
+        usages.push((options.names[0], text));
+    }
+
+    channel.send_message(&ctx.http, |cm| {
+        cm.embed(|ce| {
+            ce.title(tr(&language, "help.parties", &[]));
+            ce.description(tr(&language, "help.prefix", &[("prefix", &prefix)]));
+            for (name, text) in usages {
+                ce.field(name, text, false);
+            }
+            ce.colour(Colour::DARK_GOLD);
+            ce
+        });
+        cm
+    }).await?;
+
+    Ok(())
+}
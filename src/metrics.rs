@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use prometheus::{
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec
+};
+use serenity::model::prelude::*;
+
+lazy_static! {
+    pub(crate) static ref COMMANDS_RUN: IntCounterVec = register_int_counter_vec!(
+        "hr_bot_commands_run_total",
+        "Commands run, by name.",
+        &["command"]
+    ).unwrap();
+
+    pub(crate) static ref COMMANDS_FAILED: IntCounterVec = register_int_counter_vec!(
+        "hr_bot_commands_failed_total",
+        "Commands that returned an error, by name.",
+        &["command"]
+    ).unwrap();
+
+    pub(crate) static ref ACTIVE_PARTIES: IntGaugeVec = register_int_gauge_vec!(
+        "hr_bot_active_parties",
+        "Parties currently being looked after, by guild.",
+        &["guild"]
+    ).unwrap();
+
+    pub(crate) static ref REACTION_COLLECTORS: IntGauge = register_int_gauge!(
+        "hr_bot_reaction_collectors",
+        "Party announcements currently collecting reactions."
+    ).unwrap();
+
+    pub(crate) static ref PARTY_JOINS: IntCounter = register_int_counter!(
+        "hr_bot_party_joins_total",
+        "Members that joined a party."
+    ).unwrap();
+
+    pub(crate) static ref PARTY_LEAVES: IntCounter = register_int_counter!(
+        "hr_bot_party_leaves_total",
+        "Members that left a party, including members that left the server."
+    ).unwrap();
+
+    pub(crate) static ref AUTO_DELETIONS: IntCounter = register_int_counter!(
+        "hr_bot_party_auto_deletions_total",
+        "Parties deleted by the bot because they expired."
+    ).unwrap();
+
+    pub(crate) static ref DISCORD_API_ERRORS: IntCounter = register_int_counter!(
+        "hr_bot_discord_api_errors_total",
+        "Requests to Discord that failed."
+    ).unwrap();
+
+    pub(crate) static ref DB_LATENCY: HistogramVec = register_histogram_vec!(
+        "hr_bot_db_latency_seconds",
+        "How long database operations take, by operation.",
+        &["operation"]
+    ).unwrap();
+}
+
+// Counts the error if it came from Discord, even when it got wrapped in another error.
+pub(crate) fn record_error(error: &(dyn std::error::Error + 'static)) {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(serenity::Error::Http(_)) = error.downcast_ref::<serenity::Error>() {
+            DISCORD_API_ERRORS.inc();
+            return
+        }
+
+        current = error.source();
+    }
+}
+
+// Keeps a reaction collector counted for as long as it is alive, however its command ends.
+pub(crate) struct CollectorGuard;
+
+impl CollectorGuard {
+    pub(crate) fn new() -> Self {
+        REACTION_COLLECTORS.inc();
+        CollectorGuard
+    }
+}
+
+impl Drop for CollectorGuard {
+    fn drop(&mut self) {
+        REACTION_COLLECTORS.dec();
+    }
+}
+
+// Replaces the active party gauge with the parties currently tracked, so guilds without any
+// parties left drop out of it.
+pub(crate) fn set_active_parties<I: IntoIterator<Item = GuildId>>(guilds: I) {
+    let mut counts: HashMap<GuildId, i64> = HashMap::new();
+    for guild in guilds {
+        *counts.entry(guild).or_insert(0) += 1;
+    }
+
+    ACTIVE_PARTIES.reset();
+    for (guild, count) in counts {
+        ACTIVE_PARTIES.with_label_values(&[&guild.0.to_string()]).set(count);
+    }
+}
+
+// The metrics in the Prometheus text format, along with their content type.
+pub(crate) fn render() -> prometheus::Result<(String, Vec<u8>)> {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(&prometheus::gather(), &mut buffer)?;
+
+    Ok((encoder.format_type().to_string(), buffer))
+}
@@ -1,4 +1,6 @@
+use chrono::Utc;
 use serde::{Serialize, Deserialize};
+use crate::slots::Slot;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct Group {
@@ -12,7 +14,34 @@ pub(crate) struct Group {
     pub voice_id: i64,
     pub text_id: i64,
     pub role_id: i64,
-    pub time_til_auto_del: i64
+    // Where the party announcement lives, so it can be updated outside of the `create` command.
+    #[serde(default)]
+    pub channel_id: i64,
+    #[serde(default)]
+    pub message_id: i64,
+    #[serde(default)]
+    pub command_id: i64,
+    // Unix timestamp of when the party was created.
+    #[serde(default)]
+    pub created_at: i64,
+    // The most players the party had at once.
+    #[serde(default)]
+    peak_players: i64,
+    // What kind of game is being played, e.g. "ranked" or "draft".
+    #[serde(default)]
+    pub mode: Option<String>,
+    // Unix timestamp of when the party plans to start. It doesn't expire before then.
+    #[serde(default)]
+    pub starts_at: Option<i64>,
+    // Private parties can only be joined by the owner and the members they invited.
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub invited: Vec<i64>,
+    // Named parts of the party with their own quotas, like tank, healer and DPS. Parties without
+    // them are joined with a thumbs up.
+    #[serde(default)]
+    pub slots: Vec<Slot>
 }
 
 impl Group {
@@ -33,6 +62,7 @@ impl Group {
         group.set_voice_id(voice);
         group.set_text_id(text);
         group.set_role_id(role);
+        group.created_at = Utc::now().timestamp();
         group
     }
 
@@ -43,6 +73,7 @@ impl Group {
     pub(crate) async fn add_player(&mut self, player: i64) {
         self.current_players.push(player);
         self.player_amount += 1;
+        self.peak_players = self.peak_players.max(self.player_amount);
     }
 
     pub(crate) async fn add_player_name(&mut self, player_name: String) {
@@ -57,19 +88,42 @@ impl Group {
         for (i, curr_player) in self.current_players.iter().enumerate() {
             if *curr_player == player {
                 self.current_players.remove(i);
+                // Names are kept in the same order as the ids, and two members can share a name.
+                if i < self.player_names.len() {
+                    self.player_names.remove(i);
+                }
                 self.player_amount -= 1;
                 break
             }
         }
+
+        for slot in self.slots.iter_mut() {
+            slot.members.retain(|member| *member != player);
+        }
     }
 
-    pub(crate) async fn remove_player_name(&mut self, player_name: String) {
-        for (i, curr_player) in self.player_names.iter().enumerate() {
-            if *curr_player == player_name {
-                self.player_names.remove(i);
-                break
-            }
+    // Which slot a reaction stands for.
+    pub(crate) fn slot_index(&self, emoji: &str) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.emoji == emoji)
+    }
+
+    pub(crate) fn in_slot(&self, slot: usize, player: i64) -> bool {
+        self.slots[slot].members.contains(&player)
+    }
+
+    pub(crate) fn has_slot(&self, player: i64) -> bool {
+        self.slots.iter().any(|slot| slot.members.contains(&player))
+    }
+
+    // Puts the player in the slot, unless it is already full.
+    pub(crate) fn join_slot(&mut self, slot: usize, player: i64) -> bool {
+        let slot = &mut self.slots[slot];
+        if slot.full() {
+            return false
         }
+
+        slot.members.push(player);
+        true
     }
 
     pub(crate) fn in_player_vec(&self, player: &i64) -> bool {
@@ -77,11 +131,52 @@ impl Group {
         false
     }
 
+    pub(crate) fn player_ids(&self) -> &[i64] {
+        &self.current_players
+    }
+
+    pub(crate) fn can_join(&self, player: i64) -> bool {
+        !self.private || player == self.owner || self.invited.contains(&player)
+    }
+
+    // Parties stored before announcements were tracked have none to update or clean up.
+    pub(crate) fn has_announcement(&self) -> bool {
+        self.channel_id != 0 && self.message_id != 0
+    }
+
+    pub(crate) fn set_messages(&mut self, channel: i64, command: i64, message: i64) {
+        self.channel_id = channel;
+        self.command_id = command;
+        self.message_id = message;
+    }
+
+    // Hands the party over to the first player that joined and doesn't already own a party.
+    // Returns the new owner, or `None` if nobody is left to take it over.
+    pub(crate) fn transfer_ownership(&mut self, owners: &[i64]) -> Option<i64> {
+        for player in self.current_players.iter() {
+            if *player != self.owner && !owners.contains(player) {
+                let new_owner = *player;
+                self.set_owner(new_owner);
+                return Some(new_owner)
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub(crate) fn game(&self) -> &str {
+        &self.game
+    }
+
     fn set_owner(&mut self, owner: i64) {
         self.owner = owner;
     }
 
-    fn set_title<T: Into<String>>(&mut self, title: T) {
+    pub(crate) fn set_title<T: Into<String>>(&mut self, title: T) {
         self.title = title.into();
     }
 
@@ -105,25 +200,22 @@ impl Group {
         self.role_id = id;
     }
 
-    pub(crate) fn players(&self) -> String {
-        if self.player_names.len() == 0 { "None".to_string() }
-        else {
-            let current_string: String = self.player_names
-            .clone()
-            .into_iter()
-            .map(|mut x| { x.push_str(", "); x })
-            .collect();
-
-        current_string
-            .strip_suffix(", ")
-            .unwrap()
-            .to_string()
-        }
+    // The names of everyone in the party, or `None` while it is empty.
+    pub(crate) fn players(&self) -> Option<String> {
+        if self.player_names.is_empty() { None } else { Some(self.player_names.join(", ")) }
     }
 
     pub(crate) fn player_amount(&self) -> usize {
         self.player_amount as usize
     }
+
+    pub(crate) fn max_player_amount(&self) -> usize {
+        self.max_players as usize
+    }
+
+    pub(crate) fn peak_players(&self) -> usize {
+        self.peak_players as usize
+    }
 }
 
 impl Default for Group {
@@ -139,7 +231,16 @@ impl Default for Group {
             voice_id: 0,
             text_id: 0,
             role_id: 0,
-            time_til_auto_del: 2
+            channel_id: 0,
+            message_id: 0,
+            command_id: 0,
+            created_at: 0,
+            peak_players: 0,
+            mode: None,
+            starts_at: None,
+            private: false,
+            invited: Vec::new(),
+            slots: Vec::new()
         }
     }
 }
\ No newline at end of file
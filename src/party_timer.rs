@@ -0,0 +1,701 @@
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{Arc, atomic::{AtomicBool, Ordering}},
+    time::{Duration, Instant}
+};
+
+use chrono::Utc;
+use serenity::{
+    framework::standard::CommandResult,
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use tracing::{debug, error, instrument};
+use crate::archive;
+use crate::audit_log::{self, PartyEvent};
+use crate::db::DatabaseServer;
+use crate::embeds::{format_remaining, update_party_embed};
+use crate::i18n::{self, tr};
+use crate::metrics;
+use crate::party_groups::Group;
+use crate::sharding;
+use crate::voice;
+
+// Reacting with this on an expiry warning resets the countdown of a party.
+pub(crate) const KEEP_ALIVE: &str = "⏰";
+
+// The lifecycle every party goes through, from creation to cleanup:
+//
+// Open ──> Active <──> Idle ──> Expiring ──> Closed
+//   └──────────────────────────────┘
+//
+// Open: just created, nobody has used the voice channel yet.
+// Active: somebody is in the voice channel.
+// Idle: the voice channel emptied out, waiting to see if anyone comes back.
+// Expiring: the party has been idle for too long and is about to be deleted.
+// Closed: the party should be deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PartyState {
+    Open,
+    Active,
+    Idle,
+    Expiring,
+    Closed
+}
+
+// Built from the `party` section of the config.
+#[derive(Debug, Clone)]
+pub(crate) struct TimerConfig {
+    // How often every party gets looked at.
+    pub tick: Duration,
+    // A party needs at least this many players to count as more than a lobby.
+    pub min_players: usize,
+    // How long an open or idle party with too few players lives before it starts expiring.
+    pub lonely_timeout: Duration,
+    // How long an open or idle party with enough players lives before it starts expiring.
+    pub idle_timeout: Duration,
+    // How long a party stays expiring before it is closed.
+    pub expiry: Duration
+}
+
+// What the scheduler saw of a party on a tick.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Observation {
+    pub players: usize,
+    pub voice_occupied: bool,
+    // The last time somebody was seen in the voice channel, if ever.
+    pub voice_active_at: Option<Instant>
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Lifecycle {
+    state: PartyState,
+    since: Instant,
+    players: usize
+}
+
+impl Lifecycle {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            state: PartyState::Open,
+            since: now,
+            players: 0
+        }
+    }
+
+    pub(crate) fn state(&self) -> PartyState {
+        self.state
+    }
+
+    // How long until an expiring party gets closed.
+    pub(crate) fn expires_in(&self, now: Instant, config: &TimerConfig) -> Option<Duration> {
+        if self.state == PartyState::Expiring {
+            Some(config.expiry.checked_sub(self.elapsed(now)).unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    // How long the party has been in its current state.
+    pub(crate) fn elapsed(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.since)
+    }
+
+    // Moves the party along given what was observed at `now`, and returns its new state.
+    pub(crate) fn advance(
+        &mut self,
+        observation: Observation,
+        now: Instant,
+        config: &TimerConfig
+    ) -> PartyState {
+        if self.state == PartyState::Closed {
+            return self.state
+        }
+
+        if observation.voice_occupied {
+            self.players = observation.players;
+            self.set(PartyState::Active, now);
+            return self.state
+        }
+
+        // Somebody joining counts as a sign of life, and starts the countdown over.
+        let joined = observation.players > self.players;
+        self.players = observation.players;
+        if joined && self.state != PartyState::Active {
+            let state = if self.state == PartyState::Open { PartyState::Open } else { PartyState::Idle };
+            self.set(state, now);
+        }
+
+        match self.state {
+            PartyState::Active => {
+                let since = observation.voice_active_at.unwrap_or(now).min(now);
+                self.set(PartyState::Idle, since);
+                self.expire_if_idle(now, config);
+            },
+            PartyState::Open | PartyState::Idle => self.expire_if_idle(now, config),
+            PartyState::Expiring => {
+                if self.elapsed(now) >= config.expiry {
+                    self.set(PartyState::Closed, now);
+                }
+            },
+            PartyState::Closed => {}
+        }
+
+        self.state
+    }
+
+    // Resets the countdown of a party that isn't in use, as if it just went idle.
+    pub(crate) fn keep_alive(&mut self, now: Instant) {
+        match self.state {
+            PartyState::Open => self.set(PartyState::Open, now),
+            PartyState::Idle | PartyState::Expiring => self.set(PartyState::Idle, now),
+            PartyState::Active | PartyState::Closed => {}
+        }
+    }
+
+    fn expire_if_idle(&mut self, now: Instant, config: &TimerConfig) {
+        let timeout = if self.players < config.min_players {
+            config.lonely_timeout
+        } else {
+            config.idle_timeout
+        };
+
+        if self.elapsed(now) >= timeout {
+            self.set(PartyState::Expiring, now);
+        }
+    }
+
+    fn set(&mut self, state: PartyState, since: Instant) {
+        self.state = state;
+        self.since = since;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TrackedParty {
+    pub guild: GuildId,
+    pub lifecycle: Lifecycle,
+    // The warning posted in the party's text channel while it is expiring.
+    pub warning: Option<MessageId>,
+    pub expires_at: Option<Instant>
+}
+
+// Every party the scheduler is looking after, by the id of its role.
+pub(crate) struct PartyTimers;
+
+impl TypeMapKey for PartyTimers {
+    type Value = Arc<Mutex<HashMap<i64, TrackedParty>>>;
+}
+
+async fn timers(ctx: &Context) -> Arc<Mutex<HashMap<i64, TrackedParty>>> {
+    ctx.data
+        .read()
+        .await
+        .get::<PartyTimers>()
+        .expect("Expected PartyTimers in TypeMap.")
+        .clone()
+}
+
+// Starts looking after a party. Tracking the same party twice does nothing, and neither does
+// tracking a party of a guild that belongs to another shard.
+pub(crate) async fn track(ctx: &Context, guild: GuildId, party_id: i64) {
+    if !sharding::owns_guild(ctx, guild).await {
+        return
+    }
+
+    let timers = timers(ctx).await;
+    let mut timers = timers.lock().await;
+    timers.entry(party_id).or_insert_with(|| TrackedParty {
+        guild,
+        lifecycle: Lifecycle::new(Instant::now()),
+        warning: None,
+        expires_at: None
+    });
+}
+
+pub(crate) async fn expires_at(ctx: &Context, party_id: i64) -> Option<Instant> {
+    timers(ctx).await.lock().await.get(&party_id).and_then(|party| party.expires_at)
+}
+
+pub(crate) async fn untrack(ctx: &Context, party_id: i64) {
+    timers(ctx).await.lock().await.remove(&party_id);
+}
+
+static SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+
+// `ready` fires again for every shard and on every reconnect, so only the first call starts the
+// scheduler.
+pub(crate) fn start_scheduler(ctx: &Context, config: TimerConfig) {
+    if !SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        tokio::spawn(run_scheduler(ctx.clone(), config));
+    }
+}
+
+// The one task that drives the lifecycle of every party.
+pub(crate) async fn run_scheduler(ctx: Context, config: TimerConfig) {
+    let mut timer = tokio::time::interval(config.tick);
+
+    loop {
+        timer.tick().await;
+
+        let tracked: Vec<(i64, GuildId)> = timers(&ctx)
+            .await
+            .lock()
+            .await
+            .iter()
+            .map(|(party_id, party)| (*party_id, party.guild))
+            .collect();
+        metrics::set_active_parties(tracked.iter().map(|(_, guild)| *guild));
+
+        for (party_id, guild) in tracked {
+            if let Err(why) = check_party(&ctx, &config, guild, party_id).await {
+                metrics::record_error(&*why);
+                error!(party = party_id, error = ?why, "Could not check party");
+            }
+        }
+    }
+}
+
+#[instrument(skip(ctx, config))]
+async fn check_party(
+    ctx: &Context,
+    config: &TimerConfig,
+    guild: GuildId,
+    party_id: i64
+) -> CommandResult {
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let group = match server_data.get_party(&party_id).await {
+        Some(group) => group,
+        None => {
+            untrack(ctx, party_id).await;
+            return Ok(())
+        }
+    };
+
+    let voice_channel = ChannelId(group.voice_id as u64);
+    let observation = Observation {
+        players: group.player_amount(),
+        voice_occupied: voice::channel_occupied(ctx, guild, voice_channel).await,
+        voice_active_at: voice::last_active(ctx, voice_channel).await
+    };
+
+    let now = Instant::now();
+    let (previous, state, expires_in, warning) = {
+        let timers = timers(ctx).await;
+        let mut timers = timers.lock().await;
+        let party = match timers.get_mut(&party_id) {
+            Some(party) => party,
+            None => return Ok(())
+        };
+
+        // A party planned for later is kept open until it starts.
+        if group.starts_at.map_or(false, |starts_at| starts_at > Utc::now().timestamp()) {
+            party.lifecycle.keep_alive(now);
+            return Ok(())
+        }
+
+        let previous = party.lifecycle.state();
+        let state = party.lifecycle.advance(observation, now, config);
+        if state != previous {
+            debug!(?previous, ?state, "Party changed state");
+        }
+        let expires_in = party.lifecycle.expires_in(now, config);
+        party.expires_at = expires_in.map(|expires_in| now + expires_in);
+        (previous, state, expires_in, party.warning)
+    };
+
+    match state {
+        PartyState::Closed => {
+            metrics::AUTO_DELETIONS.inc();
+            audit_log::log(ctx, guild, &group, None, PartyEvent::Expired).await;
+            disband(ctx, guild, &group).await?
+        },
+        PartyState::Expiring => {
+            // A warning that couldn't be sent gets another try on the next tick.
+            if warning.is_none() {
+                let remaining = expires_in.unwrap_or(config.expiry);
+                let language = i18n::language_in(ctx, guild).await;
+                let warning = warn(ctx, &group, remaining, &language).await?;
+                set_warning(ctx, party_id, Some(warning)).await;
+            }
+
+            update_party_embed(ctx, guild, &group).await?;
+        },
+        _ => if previous == PartyState::Expiring {
+            clear_warning(ctx, guild, &group, warning).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Lets the members know the party is about to be deleted, and how to stop it.
+async fn warn(
+    ctx: &Context,
+    group: &Group,
+    remaining: Duration,
+    language: &str
+) -> serenity::Result<MessageId> {
+    let text_channel = ChannelId(group.text_id as u64);
+    let warning = text_channel.send_message(&ctx.http, |cm| {
+        cm.content(RoleId(group.role_id as u64).mention());
+        cm.embed(|ce| {
+            ce.title(tr(language, "party.closing_title", &[]));
+            ce.description(tr(language, "party.closing_description", &[
+                ("remaining", &format_remaining(remaining, language)),
+                ("emoji", &KEEP_ALIVE)
+            ]));
+            ce.colour(Colour::ORANGE);
+            ce
+        });
+        cm
+    }).await?;
+
+    warning.react(&ctx.http, ReactionType::try_from(KEEP_ALIVE).unwrap()).await?;
+
+    Ok(warning.id)
+}
+
+async fn set_warning(ctx: &Context, party_id: i64, warning: Option<MessageId>) {
+    if let Some(party) = timers(ctx).await.lock().await.get_mut(&party_id) {
+        party.warning = warning;
+    }
+}
+
+async fn clear_warning(
+    ctx: &Context,
+    guild: GuildId,
+    group: &Group,
+    warning: Option<MessageId>
+) -> CommandResult {
+    set_warning(ctx, group.role_id, None).await;
+    if let Some(warning) = warning {
+        ChannelId(group.text_id as u64).delete_message(&ctx.http, warning).await?;
+    }
+
+    update_party_embed(ctx, guild, group).await?;
+
+    Ok(())
+}
+
+// Any member of a party can react to its warning to reset the countdown.
+pub(crate) async fn handle_keep_alive(ctx: &Context, reaction: &Reaction) -> CommandResult {
+    if reaction.emoji.as_data() != KEEP_ALIVE {
+        return Ok(())
+    }
+
+    let user_id = match reaction.user_id {
+        Some(user_id) => user_id.0 as i64,
+        None => return Ok(())
+    };
+
+    let found = timers(ctx)
+        .await
+        .lock()
+        .await
+        .iter()
+        .find(|(_, party)| party.warning == Some(reaction.message_id))
+        .map(|(party_id, party)| (*party_id, party.guild));
+    let (party_id, guild) = match found {
+        Some(found) => found,
+        None => return Ok(())
+    };
+
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let group = match server_data.get_party(&party_id).await {
+        Some(group) => group,
+        None => return Ok(())
+    };
+
+    if group.owner != user_id && !group.in_player_vec(&user_id) {
+        return Ok(())
+    }
+
+    let warning = {
+        let timers = timers(ctx).await;
+        let mut timers = timers.lock().await;
+        match timers.get_mut(&party_id) {
+            Some(party) => {
+                party.lifecycle.keep_alive(Instant::now());
+                party.expires_at = None;
+                party.warning
+            },
+            None => return Ok(())
+        }
+    };
+
+    clear_warning(ctx, guild, &group, warning).await
+}
+
+// Archives the party if the guild wants it, removes its channels, role and messages, and forgets about it in the database.
+// Every deletion is attempted even if an earlier one fails, e.g. because someone already deleted
+// the channel by hand. Failures are counted as they happen, the error at the end only describes
+// them.
+#[instrument(skip(ctx, group), fields(party = group.role_id))]
+pub(crate) async fn disband(ctx: &Context, guild: GuildId, group: &Group) -> CommandResult {
+    let mut failures = Vec::new();
+
+    if let Err(why) = archive::archive_party(ctx, guild, group).await {
+        metrics::record_error(&*why);
+        failures.push(format!("archive: {:?}", why));
+    }
+
+    if let Err(why) = ctx.http.delete_channel(group.text_id as u64).await {
+        metrics::record_error(&why);
+        failures.push(format!("text channel: {:?}", why));
+    }
+    if let Err(why) = ctx.http.delete_channel(group.voice_id as u64).await {
+        metrics::record_error(&why);
+        failures.push(format!("voice channel: {:?}", why));
+    }
+    if let Err(why) = ctx.http.delete_role(guild.0, group.role_id as u64).await {
+        metrics::record_error(&why);
+        failures.push(format!("role: {:?}", why));
+    }
+    if group.channel_id != 0 && group.command_id != 0 {
+        if let Err(why) = ctx.http.delete_message(group.channel_id as u64, group.command_id as u64).await {
+            metrics::record_error(&why);
+            failures.push(format!("command message: {:?}", why));
+        }
+    }
+    if group.has_announcement() {
+        if let Err(why) = ctx.http.delete_message(group.channel_id as u64, group.message_id as u64).await {
+            metrics::record_error(&why);
+            failures.push(format!("party message: {:?}", why));
+        }
+    }
+
+    voice::forget(ctx, ChannelId(group.voice_id as u64)).await;
+    untrack(ctx, group.role_id).await;
+
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    server_data.record_party(group);
+    server_data.delete_party(&group.role_id).await;
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Could not fully disband party {}: {}", group.role_id, failures.join(", ")).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> TimerConfig {
+        TimerConfig {
+            tick: Duration::from_secs(30),
+            min_players: 2,
+            lonely_timeout: Duration::from_secs(60),
+            idle_timeout: Duration::from_secs(15 * 60),
+            expiry: Duration::from_secs(60)
+        }
+    }
+
+    // Somebody is in the voice channel.
+    fn talking(players: usize, now: Instant) -> Observation {
+        Observation { players, voice_occupied: true, voice_active_at: Some(now) }
+    }
+
+    // Nobody is in the voice channel, and the last time somebody was is `voice_active_at`.
+    fn quiet(players: usize, voice_active_at: Option<Instant>) -> Observation {
+        Observation { players, voice_occupied: false, voice_active_at }
+    }
+
+    // A party with enough players whose voice channel emptied out at `start`.
+    fn idle_party(start: Instant) -> Lifecycle {
+        let mut lifecycle = Lifecycle::new(start);
+        lifecycle.advance(talking(3, start), start, &config());
+        lifecycle.advance(quiet(3, Some(start)), start, &config());
+        assert_eq!(lifecycle.state(), PartyState::Idle);
+        lifecycle
+    }
+
+    // A party that started expiring at the returned instant.
+    fn expiring_party(start: Instant) -> (Lifecycle, Instant) {
+        let mut lifecycle = idle_party(start);
+        let now = start + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), now, &config()), PartyState::Expiring);
+        (lifecycle, now)
+    }
+
+    #[test]
+    fn open_becomes_active_when_the_voice_channel_is_used() {
+        let start = Instant::now();
+        let mut lifecycle = Lifecycle::new(start);
+
+        let now = start + Duration::from_secs(5);
+        assert_eq!(lifecycle.advance(talking(2, now), now, &config()), PartyState::Active);
+    }
+
+    #[test]
+    fn lonely_open_party_starts_expiring() {
+        let start = Instant::now();
+        let mut lifecycle = Lifecycle::new(start);
+        assert_eq!(lifecycle.advance(quiet(1, None), start, &config()), PartyState::Open);
+
+        let before = start + config().lonely_timeout - Duration::from_secs(1);
+        assert_eq!(lifecycle.advance(quiet(1, None), before, &config()), PartyState::Open);
+
+        let now = start + config().lonely_timeout;
+        assert_eq!(lifecycle.advance(quiet(1, None), now, &config()), PartyState::Expiring);
+    }
+
+    #[test]
+    fn active_becomes_idle_when_the_voice_channel_empties() {
+        let start = Instant::now();
+        let mut lifecycle = Lifecycle::new(start);
+        lifecycle.advance(talking(3, start), start, &config());
+
+        let now = start + Duration::from_secs(30);
+        assert_eq!(lifecycle.advance(quiet(3, Some(now)), now, &config()), PartyState::Idle);
+    }
+
+    #[test]
+    fn idle_starts_expiring_after_its_timeout() {
+        let start = Instant::now();
+        let mut lifecycle = idle_party(start);
+
+        let before = start + config().idle_timeout - Duration::from_secs(1);
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), before, &config()), PartyState::Idle);
+
+        let now = start + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), now, &config()), PartyState::Expiring);
+        assert_eq!(lifecycle.expires_in(now, &config()), Some(config().expiry));
+    }
+
+    #[test]
+    fn expiring_becomes_active_when_somebody_talks() {
+        let start = Instant::now();
+        let (mut lifecycle, now) = expiring_party(start);
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(lifecycle.advance(talking(3, later), later, &config()), PartyState::Active);
+    }
+
+    #[test]
+    fn keep_alive_takes_an_expiring_party_back_to_idle() {
+        let start = Instant::now();
+        let (mut lifecycle, now) = expiring_party(start);
+
+        let kept_at = now + Duration::from_secs(30);
+        lifecycle.keep_alive(kept_at);
+        assert_eq!(lifecycle.state(), PartyState::Idle);
+
+        // The party gets a whole idle timeout again, counted from the keep-alive.
+        let before = kept_at + config().idle_timeout - Duration::from_secs(1);
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), before, &config()), PartyState::Idle);
+
+        let deadline = kept_at + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), deadline, &config()), PartyState::Expiring);
+    }
+
+    #[test]
+    fn open_party_with_exactly_enough_players_gets_the_idle_timeout() {
+        let start = Instant::now();
+        let mut lifecycle = Lifecycle::new(start);
+        let players = config().min_players;
+        assert_eq!(lifecycle.advance(quiet(players, None), start, &config()), PartyState::Open);
+
+        let lonely = start + config().lonely_timeout;
+        assert_eq!(lifecycle.advance(quiet(players, None), lonely, &config()), PartyState::Open);
+
+        let before = start + config().idle_timeout - Duration::from_secs(1);
+        assert_eq!(lifecycle.advance(quiet(players, None), before, &config()), PartyState::Open);
+
+        let now = start + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(players, None), now, &config()), PartyState::Expiring);
+    }
+
+    #[test]
+    fn joining_an_open_party_starts_its_countdown_over() {
+        let start = Instant::now();
+        let mut lifecycle = Lifecycle::new(start);
+        lifecycle.advance(quiet(1, None), start, &config());
+
+        let joined_at = start + Duration::from_secs(50);
+        assert_eq!(lifecycle.advance(quiet(2, None), joined_at, &config()), PartyState::Open);
+
+        // Past where the lonely timeout would have run out from the start.
+        let later = start + config().lonely_timeout + Duration::from_secs(1);
+        assert_eq!(lifecycle.advance(quiet(2, None), later, &config()), PartyState::Open);
+
+        let before = joined_at + config().idle_timeout - Duration::from_secs(1);
+        assert_eq!(lifecycle.advance(quiet(2, None), before, &config()), PartyState::Open);
+
+        let deadline = joined_at + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(2, None), deadline, &config()), PartyState::Expiring);
+    }
+
+    #[test]
+    fn rejoining_an_idle_party_starts_its_countdown_over() {
+        let start = Instant::now();
+        let mut lifecycle = idle_party(start);
+
+        // Leaving isn't a sign of life, coming back is.
+        let left_at = start + Duration::from_secs(300);
+        assert_eq!(lifecycle.advance(quiet(2, Some(start)), left_at, &config()), PartyState::Idle);
+
+        let rejoined_at = start + Duration::from_secs(600);
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), rejoined_at, &config()), PartyState::Idle);
+
+        let old_deadline = start + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), old_deadline, &config()), PartyState::Idle);
+
+        let deadline = rejoined_at + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), deadline, &config()), PartyState::Expiring);
+    }
+
+    #[test]
+    fn joining_an_expiring_party_takes_it_back_to_idle() {
+        let start = Instant::now();
+        let (mut lifecycle, now) = expiring_party(start);
+
+        let joined_at = now + Duration::from_secs(30);
+        assert_eq!(lifecycle.advance(quiet(4, Some(start)), joined_at, &config()), PartyState::Idle);
+        assert_eq!(lifecycle.expires_in(joined_at, &config()), None);
+
+        let after_expiry = now + config().expiry;
+        assert_eq!(lifecycle.advance(quiet(4, Some(start)), after_expiry, &config()), PartyState::Idle);
+
+        let deadline = joined_at + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(4, Some(start)), deadline, &config()), PartyState::Expiring);
+    }
+
+    #[test]
+    fn idle_becomes_active_when_somebody_talks() {
+        let start = Instant::now();
+        let mut lifecycle = idle_party(start);
+
+        let now = start + Duration::from_secs(120);
+        assert_eq!(lifecycle.advance(talking(3, now), now, &config()), PartyState::Active);
+    }
+
+    #[test]
+    fn active_starts_expiring_when_the_voice_channel_emptied_long_ago() {
+        let start = Instant::now();
+        let mut lifecycle = Lifecycle::new(start);
+        lifecycle.advance(talking(3, start), start, &config());
+
+        // The idle time counts from when the channel was last used, not from when that was noticed.
+        let now = start + config().idle_timeout;
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), now, &config()), PartyState::Expiring);
+    }
+
+    #[test]
+    fn expiring_closes_once_the_expiry_runs_out() {
+        let start = Instant::now();
+        let (mut lifecycle, now) = expiring_party(start);
+
+        let before = now + config().expiry - Duration::from_secs(1);
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), before, &config()), PartyState::Expiring);
+
+        let after = now + config().expiry;
+        assert_eq!(lifecycle.advance(quiet(3, Some(start)), after, &config()), PartyState::Closed);
+        assert_eq!(lifecycle.advance(talking(3, after), after, &config()), PartyState::Closed);
+    }
+}
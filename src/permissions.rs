@@ -0,0 +1,66 @@
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*
+};
+use tracing::error;
+use crate::db::DatabaseServer;
+use crate::i18n::{self, tr};
+
+// Administrators can always create parties. Everyone else needs one of the roles the server
+// picked with `settings createroles`.
+pub(crate) async fn can_create(ctx: &Context, msg: &Message) -> bool {
+    let guild = match msg.guild_id {
+        Some(guild) => guild,
+        None => return false
+    };
+    let member = match msg.member(ctx).await {
+        Ok(member) => member,
+        Err(_) => return false
+    };
+
+    if let Ok(permissions) = member.permissions(ctx).await {
+        if permissions.administrator() {
+            return true
+        }
+    }
+
+    let create_roles = match DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await {
+        Ok(server_data) => server_data.settings.create_roles,
+        Err(why) => {
+            error!(guild = %guild, error = ?why, "Could not read who can create parties");
+            return false
+        }
+    };
+
+    member.roles.iter().any(|role| create_roles.contains(&(role.0 as i64)))
+}
+
+#[command]
+#[aliases(creators)]
+#[description = "Lets members with any of these roles create parties, or only administrators again."]
+#[usage = "<@role>... | clear"]
+#[example = "@Gamers @Regulars"]
+#[min_args(1)]
+async fn createroles(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let language = i18n::language_for(ctx, msg).await;
+
+    let reply = if args.current() == Some("clear") {
+        server_data.settings.create_roles.clear();
+        tr(&language, "settings.create_roles_cleared", &[])
+    } else if msg.mention_roles.is_empty() {
+        msg.channel_id.say(&ctx.http, tr(&language, "settings.create_roles_missing", &[])).await?;
+        return Ok(())
+    } else {
+        server_data.settings.create_roles = msg.mention_roles.iter().map(|role| role.0 as i64).collect();
+        let mentions: Vec<String> = msg.mention_roles.iter().map(|role| role.mention()).collect();
+        tr(&language, "settings.create_roles_set", &[("roles", &mentions.join(", "))])
+    };
+
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
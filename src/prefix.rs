@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::{command, hook}},
+    model::prelude::*,
+    prelude::*
+};
+use tracing::error;
+use crate::config::Config;
+use crate::db::DatabaseServer;
+use crate::i18n::{self, tr};
+
+// Long prefixes only get in the way, and Discord users tend to typo them.
+const MAX_PREFIX_LEN: usize = 10;
+
+// The prefix each guild picked, or `None` if it uses the default one. Filled in as guilds send
+// messages, so the database isn't read for every message.
+pub(crate) struct GuildPrefixes;
+
+impl TypeMapKey for GuildPrefixes {
+    type Value = HashMap<GuildId, Option<String>>;
+}
+
+// The framework still answers to the default prefix, so nobody gets locked out by a bad one.
+#[hook]
+pub(crate) async fn guild_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    custom_prefix(ctx, msg.guild_id?).await
+}
+
+async fn custom_prefix(ctx: &Context, guild: GuildId) -> Option<String> {
+    if let Some(prefix) = ctx.data.read().await.get::<GuildPrefixes>().unwrap().get(&guild) {
+        return prefix.clone()
+    }
+
+    // Not remembered when the database can't be reached, so the next message tries again.
+    let prefix = match DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await {
+        Ok(server_data) => server_data.settings.prefix,
+        Err(why) => {
+            error!(guild = %guild, error = ?why, "Could not read the prefix");
+            return None
+        }
+    };
+    ctx.data.write().await.get_mut::<GuildPrefixes>().unwrap().insert(guild, prefix.clone());
+
+    prefix
+}
+
+// The prefix to show people, the guild's own one if it has one.
+pub(crate) async fn prefix_for(ctx: &Context, guild: Option<GuildId>) -> String {
+    if let Some(guild) = guild {
+        if let Some(prefix) = custom_prefix(ctx, guild).await {
+            return prefix
+        }
+    }
+
+    ctx.data.read().await.get::<Config>().unwrap().prefix.clone()
+}
+
+#[command]
+#[description = "Changes the prefix the bot answers to in this server. The default prefix keeps working."]
+#[usage = "<prefix> | reset"]
+#[example = "!"]
+#[num_args(1)]
+async fn prefix(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let prefix = args.single::<String>()?;
+    let language = i18n::language_for(ctx, msg).await;
+
+    if prefix.chars().count() > MAX_PREFIX_LEN {
+        msg.channel_id.say(
+            &ctx.http,
+            tr(&language, "settings.prefix_too_long", &[("max", &MAX_PREFIX_LEN)])
+        ).await?;
+        return Ok(())
+    }
+
+    let mut server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let reply = if prefix == "reset" {
+        server_data.settings.prefix = None;
+        tr(&language, "settings.prefix_reset", &[])
+    } else {
+        server_data.settings.prefix = Some(prefix.clone());
+        tr(&language, "settings.prefix_set", &[("prefix", &prefix)])
+    };
+
+    let new_prefix = server_data.settings.prefix.clone();
+    DatabaseServer::insert_or_replace(ctx, server_data).await?;
+    ctx.data.write().await.get_mut::<GuildPrefixes>().unwrap().insert(guild, new_prefix);
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant}
+};
+
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*
+};
+use tracing::{debug, warn};
+use crate::config::{Config, PresenceConfig};
+use crate::db::DatabaseServer;
+use crate::health::ShardManagerContainer;
+use crate::i18n::{self, tr};
+
+// A status set by an owner, shown instead of the rotation until it runs out.
+pub(crate) struct TemporaryStatus;
+
+impl TypeMapKey for TemporaryStatus {
+    type Value = Option<(String, Instant)>;
+}
+
+// A temporary status can be kept for up to a day.
+const MAX_STATUS_MINUTES: u64 = 24 * 60;
+
+static ROTATION_STARTED: AtomicBool = AtomicBool::new(false);
+
+// Like the party scheduler, only the first `ready` starts the rotation.
+pub(crate) fn start_rotation(ctx: &Context, config: PresenceConfig) {
+    if !ROTATION_STARTED.swap(true, Ordering::SeqCst) {
+        tokio::spawn(rotate(ctx.clone(), config));
+    }
+}
+
+async fn rotate(ctx: Context, config: PresenceConfig) {
+    let mut timer = tokio::time::interval(Duration::from_secs(config.rotate_secs));
+    let mut next = 0;
+
+    loop {
+        timer.tick().await;
+
+        if let Some(status) = temporary_status(&ctx).await {
+            set_activity(&ctx, &status).await;
+            continue
+        }
+
+        let template = &config.activities[next % config.activities.len()];
+        next += 1;
+
+        match render(&ctx, template).await {
+            Ok(status) => set_activity(&ctx, &status).await,
+            Err(why) => warn!(error = ?why, "Could not render presence")
+        }
+    }
+}
+
+async fn temporary_status(ctx: &Context) -> Option<String> {
+    let mut data = ctx.data.write().await;
+    let status = data.get_mut::<TemporaryStatus>()?;
+    let expired = match *status {
+        Some((_, until)) => Instant::now() >= until,
+        None => return None
+    };
+
+    if expired {
+        *status = None;
+        return None
+    }
+
+    status.as_ref().map(|(text, _)| text.clone())
+}
+
+// Fills in `{parties}`, `{guilds}` and `{prefix}` from what is in the database, so every process
+// shows the same numbers when the bot is split across processes.
+async fn render(ctx: &Context, template: &str) -> mongodb::error::Result<String> {
+    if !template.contains('{') {
+        return Ok(template.to_string())
+    }
+
+    let servers = DatabaseServer::all(ctx).await?;
+    let parties: usize = servers.iter().map(|server| server.parties.len()).sum();
+    let prefix = ctx.data.read().await.get::<Config>().unwrap().prefix.clone();
+
+    Ok(template
+        .replace("{parties}", &parties.to_string())
+        .replace("{guilds}", &servers.len().to_string())
+        .replace("{prefix}", &prefix))
+}
+
+// `Context::set_activity` only changes the shard the context belongs to, so go through every
+// shard this process runs.
+async fn set_activity(ctx: &Context, status: &str) {
+    debug!(status, "Setting presence");
+    let shard_manager = ctx.data.read().await.get::<ShardManagerContainer>().unwrap().clone();
+    let shard_manager = shard_manager.lock().await;
+    for runner in shard_manager.runners.lock().await.values() {
+        runner.runner_tx.set_activity(Some(Activity::playing(status)));
+    }
+}
+
+#[command]
+#[description = "Shows a status instead of the usual rotation for a while, or goes back to it."]
+#[usage = "<minutes> <status> | clear"]
+#[example = "30 Restarting soon!"]
+#[min_args(1)]
+async fn status(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let language = i18n::language_for(ctx, msg).await;
+    if args.current() == Some("clear") {
+        ctx.data.write().await.insert::<TemporaryStatus>(None);
+        msg.channel_id.say(&ctx.http, tr(&language, "presence.cleared", &[])).await?;
+        return Ok(())
+    }
+
+    let minutes = args.single::<u64>()?;
+    if minutes == 0 || minutes > MAX_STATUS_MINUTES {
+        let reply = tr(&language, "presence.invalid_minutes", &[("max", &MAX_STATUS_MINUTES)]);
+        msg.channel_id.say(&ctx.http, reply).await?;
+        return Ok(())
+    }
+    let text = args.rest().to_string();
+    if text.is_empty() {
+        msg.channel_id.say(&ctx.http, tr(&language, "presence.no_text", &[])).await?;
+        return Ok(())
+    }
+
+    let until = Instant::now() + Duration::from_secs(minutes * 60);
+    ctx.data.write().await.insert::<TemporaryStatus>(Some((text.clone(), until)));
+    set_activity(ctx, &text).await;
+    msg.channel_id.say(&ctx.http, tr(&language, "presence.set", &[("minutes", &minutes)])).await?;
+
+    Ok(())
+}
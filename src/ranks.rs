@@ -0,0 +1,89 @@
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*
+};
+use crate::db::{DatabaseServer, ServerSettings};
+use crate::games;
+use crate::i18n::{self, tr};
+
+// Ratings are kept under the game's name in lowercase, so "valorant" and "Valorant" are the same.
+// The key is part of a field path in the database, where dots and dollar signs mean something else.
+fn rating_key(settings: &ServerSettings, game: &str) -> String {
+    games::find(&settings.games, game)
+        .map_or(game, |game| game.name.as_str())
+        .to_lowercase()
+        .replace(|c: char| c == '.' || c == '$', "")
+}
+
+// The rating a member set for a game, if they set one.
+pub(crate) fn rating(settings: &ServerSettings, member: UserId, game: &str) -> Option<u32> {
+    settings.ratings
+        .get(&member.0.to_string())
+        .and_then(|ratings| ratings.get(&rating_key(settings, game)))
+        .copied()
+}
+
+#[command]
+#[description = "Sets your rank or rating in a game, so the lfg queue can match you with people of a similar one. Without a game, lists yours."]
+#[usage = "[game] [rating|off]"]
+#[example = "Valorant 1450"]
+#[example = "\"League of Legends\" off"]
+#[max_args(2)]
+async fn rank(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let language = i18n::language_for(ctx, msg).await;
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let member = msg.author.id.0.to_string();
+
+    if args.is_empty() {
+        let mut ratings: Vec<String> = server_data.settings.ratings
+            .get(&member)
+            .map(|ratings| ratings
+                .iter()
+                .map(|(game, rating)| format!("{}: **{}**", game, rating))
+                .collect())
+            .unwrap_or_default();
+        ratings.sort();
+
+        let reply = if ratings.is_empty() {
+            tr(&language, "ranks.none", &[])
+        } else {
+            tr(&language, "ranks.list", &[("ratings", &ratings.join(", "))])
+        };
+        msg.channel_id.say(&ctx.http, reply).await?;
+        return Ok(())
+    }
+
+    let game = args.single_quoted::<String>()?;
+    let key = rating_key(&server_data.settings, &game);
+
+    let reply = match args.single::<String>() {
+        Ok(value) if value == "off" => {
+            DatabaseServer::set_rating(ctx, guild.0 as i64, &member, &key, None).await?;
+            tr(&language, "ranks.removed", &[("game", &key)])
+        },
+        Ok(value) => match value.parse::<u32>() {
+            Ok(rating) => {
+                DatabaseServer::set_rating(ctx, guild.0 as i64, &member, &key, Some(rating)).await?;
+                tr(&language, "ranks.set", &[("game", &key), ("rating", &rating)])
+            },
+            Err(_) => {
+                msg.channel_id.say(&ctx.http, tr(&language, "ranks.invalid", &[("rating", &value)])).await?;
+                return Ok(())
+            }
+        },
+        Err(_) => {
+            let reply = match rating(&server_data.settings, msg.author.id, &game) {
+                Some(rating) => tr(&language, "ranks.current", &[("game", &key), ("rating", &rating)]),
+                None => tr(&language, "ranks.unrated", &[("game", &key)])
+            };
+            msg.channel_id.say(&ctx.http, reply).await?;
+            return Ok(())
+        }
+    };
+
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
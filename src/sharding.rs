@@ -0,0 +1,23 @@
+use serenity::{
+    client::Client,
+    model::prelude::*,
+    prelude::*,
+    utils
+};
+use crate::config::ShardConfig;
+
+// Starts the shards this process is configured for. Without a shard count, Discord's recommended
+// count is used and this process runs all of them.
+pub(crate) async fn start(client: &mut Client, shards: &ShardConfig) -> serenity::Result<()> {
+    match (shards.total, shards.first, shards.last) {
+        (Some(total), Some(first), Some(last)) => client.start_shard_range([first, last], total).await,
+        (Some(total), _, _) => client.start_shards(total).await,
+        _ => client.start_autosharded().await
+    }
+}
+
+// A guild's parties are only looked after by the shard the guild belongs to, so processes running
+// other shards leave them alone.
+pub(crate) async fn owns_guild(ctx: &Context, guild: GuildId) -> bool {
+    utils::shard_id(guild.0, ctx.cache.shard_count().await) == ctx.shard_id
+}
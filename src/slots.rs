@@ -0,0 +1,86 @@
+use serde::{Serialize, Deserialize};
+use crate::error::PartyError;
+
+// Slots without an emoji of their own get numbered, so there can't be more than there are numbers.
+const NUMBERED: &[&str] = &["1️⃣", "2️⃣", "3️⃣", "4️⃣", "5️⃣", "6️⃣", "7️⃣", "8️⃣", "9️⃣"];
+
+// A named part of a party with room for `quota` players, like the healer of a raid. Each slot gets
+// joined through its own reaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Slot {
+    pub name: String,
+    pub emoji: String,
+    pub quota: u32,
+    #[serde(default)]
+    pub members: Vec<i64>
+}
+
+impl Slot {
+    pub(crate) fn full(&self) -> bool {
+        self.members.len() as u32 >= self.quota
+    }
+}
+
+// The usual roles get an emoji that says what they are.
+fn emoji_for(name: &str, index: usize) -> String {
+    match name.to_lowercase().as_str() {
+        "tank" => "🛡️",
+        "healer" | "heal" | "support" => "💚",
+        "dps" | "damage" => "⚔️",
+        _ => NUMBERED[index]
+    }.to_string()
+}
+
+// Reads slots written like `tank=1,healer=1,dps=3`. No slot can be bigger than a whole party.
+pub(crate) fn parse(spec: &str, max_players: u32) -> Result<Vec<Slot>, PartyError> {
+    let invalid = || PartyError::InvalidSlots(spec.to_string());
+
+    let mut slots: Vec<Slot> = Vec::new();
+    for (index, part) in spec.split(',').enumerate() {
+        let mut split = part.splitn(2, '=');
+        let name = split.next().unwrap_or_default().trim();
+        let quota = split
+            .next()
+            .and_then(|quota| quota.trim().parse::<u32>().ok())
+            .ok_or_else(invalid)?;
+
+        if name.is_empty()
+            || quota == 0
+            || quota > max_players
+            || index >= NUMBERED.len()
+            || slots.iter().any(|slot| slot.name.eq_ignore_ascii_case(name))
+        {
+            return Err(invalid())
+        }
+
+        slots.push(Slot {
+            name: name.to_string(),
+            emoji: emoji_for(name, index),
+            quota,
+            members: Vec::new()
+        });
+    }
+
+    // Two slots can't share a reaction, e.g. "heal" and "support".
+    for (i, slot) in slots.iter().enumerate() {
+        if slots[..i].iter().any(|other| other.emoji == slot.emoji) {
+            return Err(invalid())
+        }
+    }
+
+    Ok(slots)
+}
+
+// How many players the slots make room for, or `None` when that doesn't even fit in a `u32`.
+pub(crate) fn total(slots: &[Slot]) -> Option<u32> {
+    slots.iter().try_fold(0u32, |total, slot| total.checked_add(slot.quota))
+}
+
+// How slots get written back, for the game catalog.
+pub(crate) fn describe(slots: &[Slot]) -> String {
+    slots
+        .iter()
+        .map(|slot| format!("{} {}×{}", slot.emoji, slot.name, slot.quota))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
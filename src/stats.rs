@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use crate::db::DatabaseServer;
+use crate::i18n::{self, tr};
+
+const WEEK_SECS: i64 = 7 * 24 * 60 * 60;
+
+// How many entries the "most used" lists show.
+const TOP: usize = 5;
+
+#[command]
+#[description = "Shows how the bot gets used in this server."]
+async fn stats(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let week_ago = Utc::now().timestamp() - WEEK_SECS;
+
+    let command_counts = DatabaseServer::command_counts(ctx, guild.0 as i64).await?;
+    let mut commands: Vec<(&String, &i64)> = command_counts.iter().collect();
+    commands.sort_by(|a, b| b.1.cmp(a.1));
+
+    // Running parties count towards this week and the popular games, but only ended parties have
+    // a final size.
+    let created_this_week = server_data.party_history
+        .iter()
+        .map(|record| record.created_at)
+        .chain(server_data.parties.iter().map(|party| party.created_at))
+        .filter(|created_at| *created_at >= week_ago)
+        .count();
+
+    let mut games: HashMap<&str, usize> = HashMap::new();
+    let history_games = server_data.party_history.iter().map(|record| record.game.as_str());
+    let running_games = server_data.parties.iter().map(|party| party.game());
+    for game in history_games.chain(running_games) {
+        *games.entry(game).or_insert(0) += 1;
+    }
+    let mut games: Vec<(&str, usize)> = games.into_iter().collect();
+    games.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let ended = server_data.party_history.len();
+    let (average_size, fill_rate) = if ended == 0 {
+        (0.0, 0.0)
+    } else {
+        let players: i64 = server_data.party_history.iter().map(|record| record.peak_players).sum();
+        let fill: f64 = server_data.party_history
+            .iter()
+            .filter(|record| record.max_players > 0)
+            .map(|record| record.peak_players as f64 / record.max_players as f64)
+            .sum();
+        (players as f64 / ended as f64, fill / ended as f64 * 100.0)
+    };
+
+    let language = i18n::language_for(ctx, msg).await;
+    let none = tr(&language, "stats.none", &[]);
+    msg.channel_id.send_message(&ctx.http, |cm| {
+        cm.embed(|ce| {
+            ce.title(tr(&language, "stats.title", &[]));
+            ce.field(tr(&language, "stats.most_used_commands", &[]), top_list(
+                commands.iter().map(|(name, count)| (name.as_str(), **count as usize)),
+                &none
+            ), false);
+            ce.field(tr(&language, "stats.created_this_week", &[]), created_this_week, true);
+            ce.field(tr(&language, "stats.average_size", &[]), format!("{:.1}", average_size), true);
+            ce.field(tr(&language, "stats.average_fill_rate", &[]), format!("{:.0}%", fill_rate), true);
+            ce.field(tr(&language, "stats.popular_games", &[]), top_list(games.iter().copied(), &none), false);
+            ce.colour(Colour::DARK_GOLD);
+            ce
+        });
+        cm
+    }).await?;
+
+    Ok(())
+}
+
+fn top_list<'a, I: Iterator<Item = (&'a str, usize)>>(entries: I, none: &str) -> String {
+    let list: Vec<String> = entries
+        .take(TOP)
+        .map(|(name, count)| format!("`{}`: {}", name, count))
+        .collect();
+
+    if list.is_empty() { none.to_string() } else { list.join("\n") }
+}
@@ -0,0 +1,324 @@
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+use chrono::{Timelike, Utc};
+use serde::{Serialize, Deserialize};
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::prelude::*,
+    prelude::*,
+    utils::Colour
+};
+use tracing::{debug, error};
+use crate::config::Config;
+use crate::db::DatabaseServer;
+use crate::games::{self, Resolved};
+use crate::i18n::{self, tr};
+use crate::metrics;
+use crate::party_groups::Group;
+
+// What a member wants to hear about, kept in their guild's settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Subscriber {
+    // The games the member gets a DM for. Role subscriptions are just the game's role.
+    #[serde(default)]
+    pub games: Vec<String>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>
+}
+
+impl Subscriber {
+    fn follows(&self, game: &str) -> bool {
+        self.games.iter().any(|followed| followed.eq_ignore_ascii_case(game))
+    }
+
+    // Nothing left worth keeping around.
+    fn is_empty(&self) -> bool {
+        self.games.is_empty() && self.quiet_hours.is_none()
+    }
+}
+
+// Hours of the day (UTC) in which a member doesn't get DMs. `from` can be after `to`, for nights.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub(crate) struct QuietHours {
+    pub from: u8,
+    pub to: u8
+}
+
+impl QuietHours {
+    fn contains(&self, hour: u8) -> bool {
+        if self.from <= self.to {
+            self.from <= hour && hour < self.to
+        } else {
+            hour >= self.from || hour < self.to
+        }
+    }
+}
+
+// When each member was last sent a DM about a party, across every guild.
+pub(crate) struct LastNotified;
+
+impl TypeMapKey for LastNotified {
+    type Value = HashMap<UserId, Instant>;
+}
+
+// When each game's role was last pinged for a new party, by guild.
+pub(crate) struct LastPinged;
+
+impl TypeMapKey for LastPinged {
+    type Value = HashMap<(GuildId, String), Instant>;
+}
+
+// Whether the game's role can be pinged for a new party, starting its cooldown if so. A role
+// only gets pinged once in a while, however many parties get made for the game.
+pub(crate) async fn take_ping(ctx: &Context, guild: GuildId, game: &str) -> bool {
+    let cooldown_mins = ctx.data.read().await.get::<Config>().unwrap().party.ping_cooldown_mins;
+    let cooldown = Duration::from_secs(cooldown_mins * 60);
+    let now = Instant::now();
+
+    let mut data = ctx.data.write().await;
+    let pinged = data.get_mut::<LastPinged>().unwrap();
+    pinged.retain(|_, last| now.duration_since(*last) < cooldown);
+
+    let key = (guild, game.to_lowercase());
+    if pinged.contains_key(&key) {
+        return false
+    }
+
+    pinged.insert(key, now);
+    true
+}
+
+// Whether the member got a DM less than `cooldown` ago.
+async fn recently_notified(ctx: &Context, user: UserId, cooldown: Duration) -> bool {
+    let data = ctx.data.read().await;
+    let notified = data.get::<LastNotified>().unwrap();
+    notified.get(&user).map_or(false, |last| last.elapsed() < cooldown)
+}
+
+// Starts the member's cooldown. Only DMs that got through count, so a failed one doesn't keep the
+// next party from them.
+async fn record_notification(ctx: &Context, user: UserId, cooldown: Duration) {
+    let now = Instant::now();
+    let mut data = ctx.data.write().await;
+    let notified = data.get_mut::<LastNotified>().unwrap();
+    notified.retain(|_, last| now.duration_since(*last) < cooldown);
+    notified.insert(user, now);
+}
+
+// DMs everyone subscribed to the party's game. Private parties are only for the people invited,
+// so nobody else hears about them.
+pub(crate) async fn notify(ctx: &Context, guild: GuildId, group: &Group, link: String) {
+    if group.private {
+        return
+    }
+
+    let settings = match DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await {
+        Ok(server_data) => server_data.settings,
+        Err(why) => {
+            error!(guild = %guild, error = ?why, "Could not read who to notify");
+            return
+        }
+    };
+    let cooldown_mins = ctx.data.read().await.get::<Config>().unwrap().party.notify_cooldown_mins;
+    let cooldown = Duration::from_secs(cooldown_mins * 60);
+    let guild_name = guild.name(ctx).await.unwrap_or_default();
+    let hour = Utc::now().hour() as u8;
+
+    for (member, subscriber) in &settings.subscribers {
+        let user = match member.parse::<u64>() {
+            Ok(user) if user as i64 != group.owner => UserId(user),
+            _ => continue
+        };
+
+        if !subscriber.follows(group.game())
+            || subscriber.quiet_hours.map_or(false, |quiet| quiet.contains(hour))
+            || recently_notified(ctx, user, cooldown).await
+        {
+            continue
+        }
+
+        let language = settings.member_languages
+            .get(member)
+            .map_or_else(|| i18n::guild_language(&settings), String::as_str);
+        let sent = match user.create_dm_channel(ctx).await {
+            Ok(dm_channel) => dm_channel.send_message(&ctx.http, |cm| {
+                cm.embed(|ce| {
+                    ce.title(tr(language, "subscriptions.party_opened", &[
+                        ("game", &group.game()),
+                        ("guild", &guild_name)
+                    ]));
+                    ce.description(format!("[{}]({})", group.title(), link));
+                    ce.footer(|cf| cf.text(tr(language, "subscriptions.footer", &[])));
+                    ce.colour(Colour::DARK_GOLD);
+                    ce
+                });
+                cm
+            }).await.map(|_| ()),
+            Err(why) => Err(why)
+        };
+
+        match sent {
+            Ok(()) => record_notification(ctx, user, cooldown).await,
+            // Most likely their DMs are closed, which is up to them.
+            Err(why) => {
+                metrics::record_error(&why);
+                debug!(user = %user, error = ?why, "Could not DM a subscriber");
+            }
+        }
+    }
+}
+
+#[command]
+#[aliases(sub)]
+#[description = "Lets you know when a party for a game gets made, by DM or with the game's role. Without a game, lists your subscriptions."]
+#[usage = "[game] [dm|role]"]
+#[example = "\"League of Legends\""]
+#[example = "Valorant role"]
+#[max_args(2)]
+async fn subscribe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let language = i18n::language_for(ctx, msg).await;
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let member = msg.author.id.0.to_string();
+
+    if args.is_empty() {
+        let subscriber = server_data.settings.subscribers.get(&member).cloned().unwrap_or_default();
+        let reply = if subscriber.games.is_empty() {
+            tr(&language, "subscriptions.none", &[])
+        } else {
+            tr(&language, "subscriptions.list", &[("games", &subscriber.games.join(", "))])
+        };
+        msg.channel_id.say(&ctx.http, reply).await?;
+        return Ok(())
+    }
+
+    let name = args.single_quoted::<String>()?;
+    let delivery = args.single::<String>().unwrap_or_else(|_| "dm".to_string()).to_lowercase();
+
+    let (game, ping_role) = match games::resolve(&server_data.settings.games, &name) {
+        Resolved::Found(game) => (game.name.clone(), game.ping_role),
+        Resolved::Misspelt(suggestions) => {
+            let reply = tr(&language, "error.unknown_game", &[
+                ("game", &name),
+                ("suggestions", &suggestions.join(", "))
+            ]);
+            msg.channel_id.say(&ctx.http, reply).await?;
+            return Ok(())
+        },
+        Resolved::Unknown(name) => (name, None)
+    };
+
+    let reply = match delivery.as_str() {
+        "dm" => {
+            let mut subscriber = server_data.settings.subscribers
+                .get(&member)
+                .cloned()
+                .unwrap_or_default();
+            if !subscriber.follows(&game) {
+                subscriber.games.push(game.clone());
+            }
+            DatabaseServer::set_subscriber(ctx, guild.0 as i64, &member, Some(&subscriber)).await?;
+            tr(&language, "subscriptions.subscribed", &[("game", &game)])
+        },
+        // Announcements for the game already ping its role, so all that's left is to hand it out.
+        "role" => match ping_role {
+            Some(role) => {
+                let mut member = guild.member(&ctx.http, msg.author.id).await?;
+                member.add_role(&ctx.http, RoleId(role as u64)).await?;
+                tr(&language, "subscriptions.role_added", &[("game", &game)])
+            },
+            None => tr(&language, "subscriptions.no_role", &[("game", &game)])
+        },
+        _ => tr(&language, "subscriptions.unknown_delivery", &[("delivery", &delivery)])
+    };
+
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases(unsub)]
+#[description = "Stops letting you know about parties for a game."]
+#[usage = "<game>"]
+#[example = "\"League of Legends\""]
+#[num_args(1)]
+async fn unsubscribe(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let language = i18n::language_for(ctx, msg).await;
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let member = msg.author.id.0.to_string();
+    let name = args.single_quoted::<String>()?;
+
+    let (game, ping_role) = match games::find(&server_data.settings.games, &name) {
+        Some(game) => (game.name.clone(), game.ping_role),
+        None => (name, None)
+    };
+
+    let mut unsubscribed = false;
+    if let Some(mut subscriber) = server_data.settings.subscribers.get(&member).cloned() {
+        let before = subscriber.games.len();
+        subscriber.games.retain(|followed| !followed.eq_ignore_ascii_case(&game));
+        unsubscribed = subscriber.games.len() != before;
+
+        if unsubscribed {
+            let subscriber = Some(&subscriber).filter(|subscriber| !subscriber.is_empty());
+            DatabaseServer::set_subscriber(ctx, guild.0 as i64, &member, subscriber).await?;
+        }
+    }
+
+    if let Some(role) = ping_role.map(|role| RoleId(role as u64)) {
+        let mut member = guild.member(&ctx.http, msg.author.id).await?;
+        if member.roles.contains(&role) {
+            member.remove_role(&ctx.http, role).await?;
+            unsubscribed = true;
+        }
+    }
+
+    let reply = if unsubscribed {
+        tr(&language, "subscriptions.unsubscribed", &[("game", &game)])
+    } else {
+        tr(&language, "subscriptions.not_subscribed", &[("game", &game)])
+    };
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Sets the hours (UTC) in which you don't want any DMs about parties, or turns them off."]
+#[usage = "<from> <to> | off"]
+#[example = "23 7"]
+#[min_args(1)]
+#[max_args(2)]
+async fn quiet(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let language = i18n::language_for(ctx, msg).await;
+    let server_data = DatabaseServer::get_or_insert_new(ctx, guild.0 as i64, None).await?;
+    let member = msg.author.id.0.to_string();
+
+    let quiet_hours = if args.current() == Some("off") {
+        None
+    } else {
+        match (args.single::<u8>(), args.single::<u8>()) {
+            (Ok(from), Ok(to)) if from < 24 && to < 24 && from != to => Some(QuietHours { from, to }),
+            _ => {
+                msg.channel_id.say(&ctx.http, tr(&language, "subscriptions.invalid_hours", &[])).await?;
+                return Ok(())
+            }
+        }
+    };
+
+    let mut subscriber = server_data.settings.subscribers.get(&member).cloned().unwrap_or_default();
+    subscriber.quiet_hours = quiet_hours;
+    let subscriber = Some(&subscriber).filter(|subscriber| !subscriber.is_empty());
+    DatabaseServer::set_subscriber(ctx, guild.0 as i64, &member, subscriber).await?;
+
+    let reply = match quiet_hours {
+        Some(QuietHours { from, to }) => tr(&language, "subscriptions.quiet_set", &[("from", &from), ("to", &to)]),
+        None => tr(&language, "subscriptions.quiet_off", &[])
+    };
+    msg.channel_id.say(&ctx.http, reply).await?;
+
+    Ok(())
+}
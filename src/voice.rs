@@ -0,0 +1,49 @@
+use std::{collections::HashMap, time::Instant};
+
+use serenity::{model::prelude::*, prelude::*};
+
+// Keeps the last time somebody was seen in a voice channel, fed by `voice_state_update`.
+pub(crate) struct VoiceActivity;
+
+impl TypeMapKey for VoiceActivity {
+    type Value = HashMap<ChannelId, Instant>;
+}
+
+pub(crate) async fn record_activity(ctx: &Context, old: Option<&VoiceState>, new: &VoiceState) {
+    let now = Instant::now();
+    let mut data = ctx.data.write().await;
+    let activity = data.get_mut::<VoiceActivity>().expect("Expected VoiceActivity in TypeMap.");
+
+    // Someone leaving a channel is the last moment it was active.
+    if let Some(channel) = old.and_then(|state| state.channel_id) {
+        activity.insert(channel, now);
+    }
+
+    if let Some(channel) = new.channel_id {
+        activity.insert(channel, now);
+    }
+}
+
+pub(crate) async fn channel_occupied(ctx: &Context, guild: GuildId, channel: ChannelId) -> bool {
+    match ctx.cache.guild(guild).await {
+        Some(guild) => guild.voice_states
+            .values()
+            .any(|state| state.channel_id == Some(channel)),
+        // Without the guild in the cache we can't tell, so don't let a party die because of it.
+        None => true
+    }
+}
+
+pub(crate) async fn last_active(ctx: &Context, channel: ChannelId) -> Option<Instant> {
+    let data = ctx.data.read().await;
+    data.get::<VoiceActivity>()
+        .and_then(|activity| activity.get(&channel))
+        .copied()
+}
+
+pub(crate) async fn forget(ctx: &Context, channel: ChannelId) {
+    let mut data = ctx.data.write().await;
+    if let Some(activity) = data.get_mut::<VoiceActivity>() {
+        activity.remove(&channel);
+    }
+}